@@ -2,13 +2,23 @@
 //!
 //! Uses identity from OpenClaw config (channels.nostr.privateKey)
 
-use agent_discovery::{AgentDiscoveryClient, Capability, Protocol, ServiceCard, Status};
+use agent_discovery::vocabulary::KNOWN_CAPABILITIES;
+use agent_discovery::{
+    AgentDiscoveryClient, AvailabilityWindow, Capability, CapabilityRequirement, ChainEnd,
+    Deprecation, DiscoveryCache, DiscoveryFilter, Heartbeat, MaintenanceWindow, PaymentMethod,
+    Policy, Presence, Pricing, Protocol, RelayLimits, ServiceCard, Status, SuccessorChain,
+    KIND_SERVICE_CARD, ONLINE_WINDOW_SECS,
+};
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use nostr::key::Keys;
-use nostr::nips::nip19::{FromBech32, ToBech32};
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip19::{FromBech32, Nip19, ToBech32};
+use nostr::nips::nip65::RelayMetadata;
+use nostr::{Event, Kind, PublicKey, RelayUrl, Timestamp};
 use serde::Deserialize;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 /// Agent Bridge - Service discovery for AI agents on Nostr
 #[derive(Parser)]
@@ -24,97 +34,383 @@ struct Cli {
     #[arg(long, global = true)]
     json: bool,
 
+    /// Relay requiring NIP-42 AUTH, answered with the configured key (repeatable)
+    #[arg(long, global = true, value_name = "URL")]
+    auth_relay: Vec<String>,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Local discovery cache used by discover, lookup and status
+#[derive(Args, Clone)]
+struct CacheArgs {
+    /// Answer from the local cache if it was synced within this many seconds
+    #[arg(long, global = true, value_name = "SECS", default_value = "60")]
+    max_age: u64,
+
+    /// Answer from the local cache only, without touching the network
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    offline: bool,
+
+    /// Always ask relays; don't read or update the local cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Cache file (default: ~/.cache/agent-bridge/discovery.sqlite)
+    #[arg(long, global = true, env = "AGENT_BRIDGE_CACHE", value_name = "PATH")]
+    cache_file: Option<PathBuf>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Show your identity (from OpenClaw config)
     Whoami,
 
     /// Publish your service card
-    Publish {
-        /// Service card ID (default: derived from pubkey)
-        #[arg(long)]
-        id: Option<String>,
+    Publish(Box<PublishArgs>),
 
-        /// Agent name (uses config profile.name if not specified)
-        #[arg(long)]
-        name: Option<String>,
+    /// Send a heartbeat
+    Heartbeat(HeartbeatArgs),
 
-        /// Agent description (uses config profile.about if not specified)
-        #[arg(long)]
-        about: Option<String>,
+    /// Discover agents
+    Discover(DiscoverArgs),
 
-        /// Capability in format "id:description" (repeatable)
-        #[arg(short, long, value_name = "CAP")]
-        capability: Vec<String>,
+    /// Look up a specific agent
+    Lookup(LookupArgs),
 
-        /// Protocol in format "type:endpoint" (repeatable)
-        /// Types: dm, dvm, a2a, mcp, http
-        #[arg(short, long, value_name = "PROTO")]
-        protocol: Vec<String>,
+    /// Check if agents are online
+    Status(StatusArgs),
 
-        /// Relay URL (repeatable, uses config relays if not specified)
-        #[arg(short, long)]
-        relay: Vec<String>,
+    /// Well-known capability vocabulary
+    Capabilities {
+        #[command(subcommand)]
+        command: CapabilitiesCommand,
     },
 
-    /// Send a heartbeat
-    Heartbeat {
-        /// Status: available, busy, maintenance
-        status: String,
-
-        /// Service card ID (default: derived from pubkey)
-        #[arg(long)]
-        service_card_id: Option<String>,
+    /// Relay information
+    Relays {
+        #[command(subcommand)]
+        command: RelaysCommand,
+    },
 
-        /// Relay URL (repeatable, uses config relays if not specified)
-        #[arg(short, long)]
-        relay: Vec<String>,
+    /// Show help for a specific topic
+    Help {
+        /// Topic to get help on
+        topic: Option<String>,
     },
+}
 
-    /// Discover agents
-    Discover {
-        /// Filter by capability (repeatable, AND logic)
-        #[arg(short, long)]
-        capability: Vec<String>,
+#[derive(Args)]
+struct PublishArgs {
+    /// Service card ID (default: derived from pubkey)
+    #[arg(long)]
+    id: Option<String>,
 
-        /// Maximum results
-        #[arg(short, long, default_value = "20")]
-        limit: usize,
+    /// Agent name (uses config profile.name if not specified)
+    #[arg(long)]
+    name: Option<String>,
 
-        /// Relay URL (repeatable)
-        #[arg(short, long)]
-        relay: Vec<String>,
-    },
+    /// Agent description (uses config profile.about if not specified)
+    #[arg(long)]
+    about: Option<String>,
 
-    /// Look up a specific agent
-    Lookup {
-        /// Agent public key (npub or hex)
-        pubkey: String,
+    /// Markdown file with long-form documentation for the card
+    #[arg(long, value_name = "PATH")]
+    content_file: Option<PathBuf>,
 
-        /// Relay URL (repeatable)
-        #[arg(short, long)]
-        relay: Vec<String>,
-    },
+    /// Capability in format "id[@version]:description" (repeatable)
+    #[arg(short, long, value_name = "CAP")]
+    capability: Vec<String>,
+
+    /// Protocol in format "type:endpoint" (repeatable)
+    /// Types: dm, nip17, dvm, a2a, mcp, http, openapi, websocket, grpc
+    #[arg(short, long, value_name = "PROTO")]
+    protocol: Vec<String>,
+
+    /// Default price: free, quote, flat:<msats> or per-<token|minute|mb>:<msats>
+    #[arg(long, value_name = "PRICE")]
+    price: Option<String>,
+
+    /// Accepted payment method: lightning:<address>, zap or bolt12:<offer> (repeatable)
+    #[arg(long, value_name = "METHOD")]
+    payment: Vec<String>,
+
+    /// Let relays delete the card after this many seconds (NIP-40)
+    #[arg(long, value_name = "SECS")]
+    expires_in: Option<u64>,
+
+    /// Weekly availability "days HH:MM-HH:MM [offset]" (repeatable),
+    /// e.g. "mon-fri 09:00-17:00 +01:00"
+    #[arg(long, value_name = "WINDOW")]
+    hours: Vec<String>,
+
+    /// Planned maintenance "start-end" in unix seconds (repeatable)
+    #[arg(long, value_name = "START-END")]
+    maintenance: Vec<String>,
+
+    /// Underlying model family (e.g. llama-3.1)
+    #[arg(long)]
+    model: Option<String>,
+
+    /// Supported modality: text, image, audio, video (repeatable)
+    #[arg(long)]
+    modality: Vec<String>,
+
+    /// Maximum context size in tokens
+    #[arg(long, value_name = "TOKENS")]
+    context_window: Option<u64>,
+
+    /// Human language as an ISO 639-1 code, e.g. "de" (repeatable)
+    #[arg(long, value_name = "LANG")]
+    language: Vec<String>,
+
+    /// Terms of service URL
+    #[arg(long, value_name = "URL")]
+    terms: Option<String>,
+
+    /// Privacy policy URL
+    #[arg(long, value_name = "URL")]
+    privacy: Option<String>,
+
+    /// Data retention: none, <days>d or indefinite
+    #[arg(long)]
+    retention: Option<String>,
+
+    /// Whether submitted data is used for training: no, opt-in, opt-out, yes
+    #[arg(long)]
+    training: Option<String>,
+
+    /// Jurisdiction the agent operates in, e.g. "EU" or "US-CA" (repeatable)
+    #[arg(long)]
+    region: Vec<String>,
+
+    /// Mark the card as deprecated, with an optional reason
+    #[arg(long, value_name = "REASON", num_args = 0..=1, default_missing_value = "")]
+    deprecated: Option<String>,
+
+    /// Seconds from now until the agent stops serving (implies --deprecated)
+    #[arg(long, value_name = "SECS")]
+    sunset_in: Option<u64>,
+
+    /// Replacement card: a "31990:<pubkey>:<d>" coordinate, or a card ID
+    /// under your own pubkey (implies --deprecated)
+    #[arg(long, value_name = "COORD")]
+    successor: Option<String>,
+
+    /// Move capabilities that exceed the relay limits into linked overflow events
+    #[arg(long)]
+    split: bool,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Mine NIP-13 proof of work of this many leading zero bits into the card
+    #[arg(long, value_name = "BITS", default_value = "0")]
+    pow: u8,
+
+    /// Add the publish relays to your NIP-65 relay list (kind 10002) so
+    /// clients can find the card by pubkey
+    #[arg(long)]
+    relay_list: bool,
+
+    /// With --relay-list, publish even if no existing relay list was found
+    #[arg(long, requires = "relay_list")]
+    force: bool,
+
+    /// Relay URL (repeatable, uses config relays if not specified)
+    #[arg(short, long)]
+    relay: Vec<String>,
+}
+
+/// Relay limits to check events against (NIP-11 `limitation` fields)
+#[derive(Args, Clone, Default)]
+struct LimitArgs {
+    /// Maximum tags per event [default: from relays, or 100]
+    #[arg(long, value_name = "N")]
+    max_event_tags: Option<usize>,
+
+    /// Maximum content length in characters [default: from relays, or 8196]
+    #[arg(long, value_name = "N")]
+    max_content_length: Option<usize>,
+
+    /// Maximum message size in bytes [default: from relays, or 16384]
+    #[arg(long, value_name = "BYTES")]
+    max_message_length: Option<usize>,
+}
+
+impl LimitArgs {
+    /// Limits given on the command line, then those the relays advertise,
+    /// conservative defaults otherwise.
+    fn limits(&self, advertised: Option<RelayLimits>) -> RelayLimits {
+        let defaults = advertised.unwrap_or_default();
+        RelayLimits {
+            max_message_length: self.max_message_length.or(defaults.max_message_length),
+            max_event_tags: self.max_event_tags.or(defaults.max_event_tags),
+            max_content_length: self.max_content_length.or(defaults.max_content_length),
+        }
+    }
+}
+
+#[derive(Args)]
+struct HeartbeatArgs {
+    /// Status: available, busy, maintenance, draining, offline
+    status: String,
+
+    /// Service card ID (default: derived from pubkey)
+    #[arg(long)]
+    service_card_id: Option<String>,
+
+    /// Current load, 0-100
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    load: Option<u8>,
+
+    /// Jobs waiting in the queue
+    #[arg(long)]
+    queue: Option<u32>,
+
+    /// Maximum concurrent jobs
+    #[arg(long)]
+    capacity: Option<u32>,
+
+    /// Agent software version
+    #[arg(long)]
+    agent_version: Option<String>,
+
+    /// Seconds since the agent started
+    #[arg(long, value_name = "SECS")]
+    uptime: Option<u64>,
+
+    /// Human-readable status message
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Per-capability status override "capability=status" (repeatable),
+    /// e.g. "audio.transcription=busy"
+    #[arg(long, value_name = "CAP=STATUS")]
+    capability_status: Vec<String>,
+
+    /// Expected return, in seconds from now (e.g. end of maintenance)
+    #[arg(long, value_name = "SECS")]
+    return_in: Option<u64>,
+
+    /// Relay URL (repeatable, uses config relays if not specified)
+    #[arg(short, long)]
+    relay: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+struct DiscoverArgs {
+    /// Filter by capability, optionally with a version requirement such as
+    /// "translation@^2" (repeatable, AND logic)
+    #[arg(short, long)]
+    capability: Vec<String>,
+
+    /// Only agents with a capability accepting this input type (e.g. audio/wav)
+    #[arg(long, value_name = "MIME")]
+    accepts: Option<String>,
+
+    /// Only agents with a capability producing this output type (e.g. text/plain)
+    #[arg(long, value_name = "MIME")]
+    produces: Option<String>,
+
+    /// Only agents with a fresh heartbeat reporting the requested capabilities available
+    #[arg(long)]
+    online: bool,
+
+    /// Only agents with a declared price at or below this many sats
+    /// (per job, or per unit for per-unit pricing)
+    #[arg(long, value_name = "SATS")]
+    max_price: Option<u64>,
+
+    /// Only agents working in this language, e.g. "de" (repeatable, AND logic)
+    #[arg(long, value_name = "LANG")]
+    language: Vec<String>,
+
+    /// Only agents handling this modality, e.g. "image" (repeatable, AND logic)
+    #[arg(long)]
+    modality: Vec<String>,
+
+    /// Only agents with at least this context size in tokens
+    #[arg(long, value_name = "TOKENS")]
+    min_context: Option<u64>,
+
+    /// Only agents that declare a data retention policy
+    #[arg(long)]
+    require_retention: bool,
+
+    /// Only agents operating solely in these regions (repeatable);
+    /// agents that declare no region are excluded
+    #[arg(long)]
+    region: Vec<String>,
+
+    /// Only cards with at least this much NIP-13 proof of work
+    #[arg(long, value_name = "BITS", default_value = "0")]
+    min_pow: u8,
+
+    /// Maximum results
+    #[arg(short, long, default_value = "20")]
+    limit: usize,
+
+    /// Relay URL (repeatable)
+    #[arg(short, long)]
+    relay: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+struct LookupArgs {
+    /// Agent public key (npub, nprofile or hex) or card address (naddr)
+    pubkey: String,
+
+    /// Show a deprecated card instead of following it to its successor
+    #[arg(long)]
+    no_follow: bool,
+
+    /// Render the card's full documentation
+    #[arg(long)]
+    full: bool,
+
+    /// Card ID to show when the agent publishes several cards
+    #[arg(long, value_name = "D")]
+    id: Option<String>,
+
+    /// Relay URL (repeatable)
+    #[arg(short, long)]
+    relay: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+struct StatusArgs {
+    /// Agent public keys (npub, nprofile or hex) or card addresses (naddr)
+    #[arg(value_name = "AGENT")]
+    agents: Vec<String>,
+
+    /// File listing agents, one per line ('#' starts a comment)
+    #[arg(short, long, value_name = "PATH")]
+    file: Option<PathBuf>,
 
-    /// Check if an agent is online
-    Status {
-        /// Agent public key (npub or hex)
-        pubkey: String,
+    /// Relay URL (repeatable)
+    #[arg(short, long)]
+    relay: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum CapabilitiesCommand {
+    /// List well-known capability ids and their aliases
+    List,
+}
 
+#[derive(Subcommand)]
+enum RelaysCommand {
+    /// Show the relays' NIP-11 documents and limitations
+    Info {
         /// Relay URL (repeatable)
         #[arg(short, long)]
         relay: Vec<String>,
     },
-
-    /// Show help for a specific topic
-    Help {
-        /// Topic to get help on
-        topic: Option<String>,
-    },
 }
 
 // ============================================================================
@@ -162,6 +458,12 @@ const DEFAULT_RELAYS: &[&str] = &[
     "wss://relay.nostr.band",
 ];
 
+/// `status` exit code when only some of the agents are online
+const EXIT_SOME_ONLINE: u8 = 2;
+
+/// `status` exit code when none of the agents are online
+const EXIT_NONE_ONLINE: u8 = 3;
+
 fn get_config_path(cli_path: Option<PathBuf>) -> Result<PathBuf> {
     if let Some(path) = cli_path {
         return Ok(path);
@@ -242,12 +544,277 @@ fn get_relays(cli_relays: Vec<String>, config_relays: &[String]) -> Vec<&'static
     }
 }
 
+/// Key to connect with, relays to authenticate to (NIP-42) and cache options
+struct Connection {
+    keys: Keys,
+    auth_relays: Vec<String>,
+    cache: CacheArgs,
+}
+
+impl Connection {
+    /// Connection for read-only commands: anonymous unless a relay needs AUTH,
+    /// which takes the configured key.
+    fn reader(config_path: &PathBuf, auth_relays: Vec<String>, cache: CacheArgs) -> Result<Self> {
+        let keys = if auth_relays.is_empty() {
+            Keys::generate()
+        } else {
+            load_identity(config_path)
+                .context("--auth-relay needs a private key in the OpenClaw config")?
+                .keys
+        };
+        Ok(Connection {
+            keys,
+            auth_relays,
+            cache,
+        })
+    }
+
+    async fn open(&self, relays: &[&str]) -> Result<AgentDiscoveryClient> {
+        if self.cache.offline {
+            anyhow::bail!("This command needs the network, drop --offline");
+        }
+        let client = AgentDiscoveryClient::new(self.keys.clone()).await?;
+        client.connect(relays).await?;
+        if !self.auth_relays.is_empty() {
+            let auth_relays: Vec<&str> = self.auth_relays.iter().map(String::as_str).collect();
+            client.connect_authenticated(&auth_relays).await?;
+        }
+        Ok(client)
+    }
+
+    /// Where to read cards and heartbeats from: the local cache, synced
+    /// first if older than --max-age, or the relays if the cache is off or
+    /// can't be used.
+    async fn source(&self, relays: &[&str]) -> Result<Source> {
+        if self.cache.no_cache {
+            return Ok(Source::Relays(Box::new(self.open(relays).await?)));
+        }
+
+        let mut cache = match open_cache(&self.cache) {
+            Ok(cache) => cache,
+            Err(e) if !self.cache.offline => {
+                eprintln!("warning: local cache unavailable, asking relays: {e:#}");
+                return Ok(Source::Relays(Box::new(self.open(relays).await?)));
+            }
+            Err(e) => return Err(e),
+        };
+        if self.cache.offline {
+            return Ok(Source::Cache(cache));
+        }
+
+        // Fresh if every relay we'd ask was synced recently
+        let now = Timestamp::now().as_u64();
+        let mut fresh = true;
+        for relay in relays.iter().copied().chain(self.auth_relays.iter().map(String::as_str)) {
+            let synced = cache.last_sync(&RelayUrl::parse(relay)?)?;
+            fresh &= synced.is_some_and(|at| now.saturating_sub(at.as_u64()) <= self.cache.max_age);
+        }
+        if fresh {
+            return Ok(Source::Cache(cache));
+        }
+
+        let client = self.open(relays).await?;
+        match client.sync_cache(&mut cache).await {
+            Ok(_) => {
+                client.disconnect().await?;
+                Ok(Source::Cache(cache))
+            }
+            Err(e) => {
+                eprintln!("warning: failed to update the local cache, asking relays: {e}");
+                Ok(Source::Relays(Box::new(client)))
+            }
+        }
+    }
+
+    /// Switch from the cache to the relays when it lacks an agent, which may
+    /// publish only to its own relays or those in a link's hints.
+    async fn on_miss(&self, source: Source, relays: &[&str]) -> Result<Source> {
+        match source {
+            Source::Cache(_) if !self.cache.offline => {
+                eprintln!("note: not in the local cache, asking relays");
+                Ok(Source::Relays(Box::new(self.open(relays).await?)))
+            }
+            source => Ok(source),
+        }
+    }
+}
+
+/// Open the cache file, creating its directory.
+fn open_cache(args: &CacheArgs) -> Result<DiscoveryCache> {
+    let path = match &args.cache_file {
+        Some(path) => path.clone(),
+        None => {
+            let home = std::env::var("HOME").context("HOME not set")?;
+            PathBuf::from(home).join(".cache/agent-bridge/discovery.sqlite")
+        }
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(DiscoveryCache::open(&path)?)
+}
+
+/// Cards and heartbeats from the relays or the local cache
+enum Source {
+    Relays(Box<AgentDiscoveryClient>),
+    Cache(DiscoveryCache),
+}
+
+impl Source {
+    async fn discover_agents(&self, filter: &DiscoveryFilter) -> Result<Vec<(ServiceCard, Event)>> {
+        Ok(match self {
+            Source::Relays(client) => client.discover_agents(filter).await?,
+            Source::Cache(cache) => cache.discover_agents(filter)?,
+        })
+    }
+
+    async fn get_service_card(
+        &self,
+        coordinate: &Coordinate,
+    ) -> Result<Option<(ServiceCard, Event)>> {
+        Ok(match self {
+            Source::Relays(client) => client.get_service_card(coordinate).await?,
+            Source::Cache(cache) => cache.get_service_card(coordinate)?,
+        })
+    }
+
+    async fn get_service_cards(&self, author: PublicKey) -> Result<Vec<(ServiceCard, Event)>> {
+        Ok(match self {
+            Source::Relays(client) => client.get_service_cards(author).await?,
+            Source::Cache(cache) => cache.get_service_cards(author)?,
+        })
+    }
+
+    async fn get_service_cards_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(ServiceCard, Event)>> {
+        Ok(match self {
+            Source::Relays(client) => client.get_service_cards_by_authors(authors).await?,
+            Source::Cache(cache) => cache.get_service_cards_by_authors(authors)?,
+        })
+    }
+
+    async fn get_heartbeats_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(Heartbeat, Event)>> {
+        Ok(match self {
+            Source::Relays(client) => client.get_heartbeats_by_authors(authors).await?,
+            Source::Cache(cache) => cache.get_heartbeats_by_authors(authors)?,
+        })
+    }
+
+    async fn follow_successors(&self, card: ServiceCard, event: Event) -> Result<SuccessorChain> {
+        Ok(match self {
+            Source::Relays(client) => client.follow_successors(card, event).await?,
+            Source::Cache(cache) => cache.follow_successors(card, event)?,
+        })
+    }
+
+    /// Also query the relays agents publish to: their NIP-65 write relays and
+    /// the DM relays of cards already seen. The cache has no relays to add.
+    async fn connect_outbox(&self, pubkeys: &[PublicKey], cards: &[ServiceCard]) -> Result<()> {
+        let Source::Relays(client) = self else {
+            return Ok(());
+        };
+        let added = client.connect_outbox(pubkeys, cards).await?;
+        if !added.is_empty() {
+            eprintln!("note: also querying {} relay(s) the agent publishes to", added.len());
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        if let Source::Relays(client) = self {
+            client.disconnect().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Agent given on the command line, with any relay hints it carries
+struct Target {
+    pubkey: PublicKey,
+    /// Card id, when a specific card was given as an naddr
+    card_id: Option<String>,
+    relays: Vec<String>,
+}
+
+impl Target {
+    /// Whether a card belongs to this target.
+    fn wants(&self, author: PublicKey, card_id: &str) -> bool {
+        self.pubkey == author && self.card_id.as_ref().is_none_or(|id| id == card_id)
+    }
+
+    /// The card coordinate, when a specific card was given.
+    fn coordinate(&self) -> Option<Coordinate> {
+        let card_id = self.card_id.as_ref()?;
+        Some(Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), self.pubkey).identifier(card_id))
+    }
+}
+
+/// Parse an npub, nprofile, naddr or hex public key, with or without a
+/// `nostr:` prefix.
+fn parse_target(s: &str) -> Result<Target> {
+    let s = s.strip_prefix("nostr:").unwrap_or(s);
+    if !s.starts_with("npub") && !s.starts_with("nprofile") && !s.starts_with("naddr") {
+        return Ok(Target {
+            pubkey: PublicKey::from_hex(s)?,
+            card_id: None,
+            relays: Vec::new(),
+        });
+    }
+
+    Ok(match Nip19::from_bech32(s)? {
+        Nip19::Pubkey(pubkey) => Target {
+            pubkey,
+            card_id: None,
+            relays: Vec::new(),
+        },
+        Nip19::Profile(profile) => Target {
+            pubkey: profile.public_key,
+            card_id: None,
+            relays: profile.relays.iter().map(|r| r.to_string()).collect(),
+        },
+        Nip19::Coordinate(coordinate) => {
+            if coordinate.kind != Kind::Custom(KIND_SERVICE_CARD) {
+                anyhow::bail!("naddr points at kind {}, not a service card", coordinate.kind);
+            }
+            Target {
+                pubkey: coordinate.public_key,
+                card_id: Some(coordinate.identifier),
+                relays: coordinate.relays.iter().map(|r| r.to_string()).collect(),
+            }
+        }
+        _ => anyhow::bail!("Expected an npub, nprofile or naddr: {s}"),
+    })
+}
+
+/// Add relay hints to the relays to query.
+fn with_hints(mut relays: Vec<&'static str>, hints: &[String]) -> Vec<&'static str> {
+    for hint in hints {
+        if !relays.contains(&hint.as_str()) {
+            relays.push(Box::leak(hint.clone().into_boxed_str()));
+        }
+    }
+    relays
+}
+
 fn parse_capability(s: &str) -> Result<Capability> {
     let parts: Vec<&str> = s.splitn(2, ':').collect();
     if parts.len() != 2 {
-        anyhow::bail!("Capability must be in format 'id:description'");
+        anyhow::bail!("Capability must be in format 'id[@version]:description'");
+    }
+    match parts[0].split_once('@') {
+        Some((id, version)) => {
+            let version = semver::Version::parse(version)
+                .with_context(|| format!("Invalid capability version '{version}'"))?;
+            Ok(Capability::new(id, parts[1]).version(version))
+        }
+        None => Ok(Capability::new(parts[0], parts[1])),
     }
-    Ok(Capability::new(parts[0], parts[1]))
 }
 
 fn parse_protocol(s: &str) -> Result<Protocol> {
@@ -257,15 +824,69 @@ fn parse_protocol(s: &str) -> Result<Protocol> {
     }
     let endpoint = parts[1];
     match parts[0] {
-        "dm" => Ok(Protocol::dm(endpoint)),
-        "dvm" => Ok(Protocol::dvm(endpoint, vec![])),
+        "dm" => Ok(Protocol::dm(Protocol::parse_relays(endpoint)?)),
+        "nip17" => Ok(Protocol::nip17(Protocol::parse_relays(endpoint)?)),
+        "dvm" => Ok(Protocol::dvm(Protocol::parse_relays(endpoint)?, vec![])),
         "a2a" => Ok(Protocol::a2a(endpoint)),
         "mcp" => Ok(Protocol::mcp(endpoint)),
         "http" => Ok(Protocol::http(endpoint)),
+        "openapi" => Ok(Protocol::openapi(endpoint)),
+        "websocket" => Ok(Protocol::websocket(endpoint)),
+        "grpc" => Ok(Protocol::grpc(endpoint)),
         other => Ok(Protocol::custom(other, endpoint)),
     }
 }
 
+fn parse_hours(s: &str) -> Result<AvailabilityWindow> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if !(2..=3).contains(&parts.len()) {
+        anyhow::bail!("Hours must be in format 'days HH:MM-HH:MM [offset]'");
+    }
+    Ok(AvailabilityWindow::parse(parts[0], parts[1], parts.get(2).copied())?)
+}
+
+fn parse_maintenance(s: &str) -> Result<MaintenanceWindow> {
+    let (start, end) = s
+        .split_once('-')
+        .context("Maintenance must be in format 'start-end'")?;
+    let start: u64 = start.parse().context("Invalid maintenance start")?;
+    let end: u64 = end.parse().context("Invalid maintenance end")?;
+    if end <= start {
+        anyhow::bail!("Maintenance must end after it starts");
+    }
+    Ok(MaintenanceWindow {
+        start: Timestamp::from(start),
+        end: Timestamp::from(end),
+        reason: None,
+    })
+}
+
+fn parse_payment(s: &str) -> Result<PaymentMethod> {
+    let (id, value) = match s.split_once(':') {
+        Some((id, value)) => (id, Some(value)),
+        None => (s, None),
+    };
+    Ok(PaymentMethod::parse(id, value)?)
+}
+
+/// Render a price for humans, e.g. "100 sats per job".
+fn format_price(pricing: &Pricing) -> String {
+    let sats = |msats: u64| {
+        if msats.is_multiple_of(1000) {
+            format!("{} sats", msats / 1000)
+        } else {
+            format!("{:.3} sats", msats as f64 / 1000.0)
+        }
+    };
+
+    match pricing {
+        Pricing::Free => "free".to_string(),
+        Pricing::Flat { msats } => format!("{} per job", sats(*msats)),
+        Pricing::PerUnit { msats, unit } => format!("{} per {unit}", sats(*msats)),
+        Pricing::Quote => "quote on request".to_string(),
+    }
+}
+
 // ============================================================================
 // Commands
 // ============================================================================
@@ -294,77 +915,265 @@ fn cmd_whoami(identity: &Identity, json: bool) -> Result<()> {
 
 async fn cmd_publish(
     identity: &Identity,
-    id: Option<String>,
-    name: Option<String>,
-    about: Option<String>,
-    capabilities: Vec<String>,
-    protocols: Vec<String>,
-    relays: Vec<String>,
+    connection: &Connection,
+    args: PublishArgs,
     json_output: bool,
 ) -> Result<()> {
-    let relays = get_relays(relays, &identity.relays);
+    let relays = get_relays(args.relay, &identity.relays);
 
-    let card_id = id.unwrap_or_else(|| format!("{}-v1", &identity.pubkey_hex[..8]));
-    let card_name = name.unwrap_or_else(|| identity.name.clone());
-    let card_about = about.unwrap_or_else(|| identity.about.clone());
+    let card_id = args
+        .id
+        .unwrap_or_else(|| format!("{}-v1", &identity.pubkey_hex[..8]));
+    let card_name = args.name.unwrap_or_else(|| identity.name.clone());
+    let card_about = args.about.unwrap_or_else(|| identity.about.clone());
 
     let mut builder = ServiceCard::builder(&card_id, &card_name).about(&card_about);
 
-    for cap_str in &capabilities {
-        let cap = parse_capability(cap_str)?;
-        builder = builder.capability(cap.id, cap.description);
+    if let Some(path) = &args.content_file {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read content file: {}", path.display()))?;
+        builder = builder.content(content);
+    }
+
+    for cap_str in &args.capability {
+        builder = builder.add_capability(parse_capability(cap_str)?);
     }
 
-    for proto_str in &protocols {
+    for proto_str in &args.protocol {
         let proto = parse_protocol(proto_str)?;
         builder = builder.protocol(proto);
     }
 
+    if let Some(price) = &args.price {
+        builder = builder.pricing(price.parse()?);
+    }
+
+    for payment_str in &args.payment {
+        builder = builder.payment_method(parse_payment(payment_str)?);
+    }
+
+    if let Some(secs) = args.expires_in {
+        builder = builder.expiration(Timestamp::from(Timestamp::now().as_u64() + secs));
+    }
+
+    for window in &args.hours {
+        builder = builder.availability(parse_hours(window)?);
+    }
+
+    for window in &args.maintenance {
+        builder = builder.maintenance(parse_maintenance(window)?);
+    }
+
+    if let Some(model) = args.model {
+        builder = builder.model(model);
+    }
+    for modality in args.modality {
+        builder = builder.modality(modality);
+    }
+    if let Some(tokens) = args.context_window {
+        builder = builder.context_window(tokens);
+    }
+    for lang in args.language {
+        builder = builder.language(lang);
+    }
+
+    if args.deprecated.is_some() || args.sunset_in.is_some() || args.successor.is_some() {
+        let mut deprecation = Deprecation::new();
+        if let Some(reason) = args.deprecated.filter(|r| !r.is_empty()) {
+            deprecation = deprecation.reason(reason);
+        }
+        if let Some(secs) = args.sunset_in {
+            deprecation = deprecation.sunset(Timestamp::from(Timestamp::now().as_u64() + secs));
+        }
+        if let Some(successor) = &args.successor {
+            deprecation = deprecation.successor(if successor.contains(':') {
+                Deprecation::parse_successor(successor)?
+            } else {
+                Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), identity.keys.public_key())
+                    .identifier(successor)
+            });
+        }
+        builder = builder.deprecation(deprecation);
+    }
+
+    builder = builder.policy(Policy {
+        terms: args.terms,
+        privacy: args.privacy,
+        retention: args.retention.as_deref().map(str::parse).transpose()?,
+        training: args.training.as_deref().map(str::parse).transpose()?,
+        regions: args.region,
+    });
+
     let card = builder.build();
 
-    let client = AgentDiscoveryClient::new(identity.keys.clone()).await?;
-    client.connect(&relays).await?;
+    for warning in card.validate()? {
+        eprintln!("warning: {warning}");
+    }
+
+    let client = connection.open(&relays).await?.pow_difficulty(args.pow);
+    if args.pow > 0 && !json_output {
+        eprintln!("Mining proof of work ({} bits)...", args.pow);
+    }
+
+    // Look up the list before publishing anything, as ours replaces it
+    let existing_list = if args.relay_list {
+        Some(find_relay_list(identity, connection, &relays, args.force).await?)
+    } else {
+        None
+    };
+
+    let limits = args.limits.limits(client.relay_limits());
+    if !args.split {
+        let warnings = card.check_budget(&limits);
+        for warning in &warnings {
+            eprintln!("warning: {warning}");
+        }
+        if !warnings.is_empty() {
+            eprintln!("hint: --split moves capabilities into linked overflow events");
+        }
+    }
+
+    let event_ids = if args.split {
+        client.publish_service_card_split(&card, &limits).await?
+    } else {
+        vec![client.publish_service_card(&card).await?]
+    };
 
-    let event_id = client.publish_service_card(&card).await?;
+    // Merge into the existing list, which the new event replaces
+    let mut relay_list_id = None;
+    if let Some(mut list) = existing_list {
+        let mut changed = false;
+        for relay in &relays {
+            let url = RelayUrl::parse(relay)?;
+            match list.iter_mut().find(|(known, _)| *known == url) {
+                Some((_, metadata)) if *metadata == Some(RelayMetadata::Read) => {
+                    *metadata = None;
+                    changed = true;
+                }
+                Some(_) => {}
+                None => {
+                    list.push((url, None));
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            relay_list_id = Some(client.publish_relay_list(&list).await?);
+        }
+    }
 
     client.disconnect().await?;
 
+    let event_id = event_ids[0];
+    let overflow: Vec<String> = event_ids[1..].iter().map(|id| id.to_hex()).collect();
+    let naddr = card.to_naddr(identity.keys.public_key(), &relays)?;
+
     if json_output {
         let output = serde_json::json!({
             "event_id": event_id.to_hex(),
+            "overflow_event_ids": overflow,
             "service_card_id": card_id,
+            "naddr": naddr,
             "relays": relays,
+            "relay_list_event_id": relay_list_id.map(|id| id.to_hex()),
         });
         println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
         println!("✓ Published service card");
         println!("  ID:       {}", card_id);
         println!("  Event:    {}", event_id.to_hex());
-        println!(
-            "  Relays:   {}",
-            relays.iter().copied().collect::<Vec<_>>().join(", ")
-        );
-    }
+        if !overflow.is_empty() {
+            println!("  Overflow: {} event(s)", overflow.len());
+        }
+        println!("  Relays:   {}", relays.join(", "));
+        println!("  Share:    nostr:{naddr}");
+        if let Some(id) = relay_list_id {
+            println!("  Relay list updated: {}", id.to_hex());
+        } else if args.relay_list {
+            println!("  Relay list already includes these relays");
+        }
+    }
 
     Ok(())
 }
 
+/// Our current NIP-65 relay list, looked up on the publish and configured
+/// relays and on the outbox relays found there.
+///
+/// Fails when none is found unless `force`: the list published next would
+/// replace one these relays merely missed.
+async fn find_relay_list(
+    identity: &Identity,
+    connection: &Connection,
+    relays: &[&'static str],
+    force: bool,
+) -> Result<Vec<(RelayUrl, Option<RelayMetadata>)>> {
+    let pubkey = identity.keys.public_key();
+    let client = connection
+        .open(&with_hints(relays.to_vec(), &identity.relays))
+        .await?;
+    client.connect_outbox(&[pubkey], &[]).await?;
+    let list = client.get_relay_list(pubkey).await?;
+    client.disconnect().await?;
+
+    if list.is_empty() && !force {
+        anyhow::bail!(
+            "No existing relay list found; publishing would replace any list these relays \
+             missed. Use --force to publish one with just the publish relays"
+        );
+    }
+    Ok(list)
+}
+
 async fn cmd_heartbeat(
     identity: &Identity,
-    status_str: String,
-    service_card_id: Option<String>,
-    relays: Vec<String>,
+    connection: &Connection,
+    args: HeartbeatArgs,
     json_output: bool,
 ) -> Result<()> {
-    let relays = get_relays(relays, &identity.relays);
+    let relays = get_relays(args.relay, &identity.relays);
 
+    let status_str = args.status;
     let status: Status = status_str.parse()?;
-    let card_id = service_card_id.unwrap_or_else(|| format!("{}-v1", &identity.pubkey_hex[..8]));
+    if let Status::Other(other) = &status {
+        eprintln!("warning: non-standard status '{other}'; clients will not treat it as available");
+    }
+    let card_id = args
+        .service_card_id
+        .unwrap_or_else(|| format!("{}-v1", &identity.pubkey_hex[..8]));
 
-    let client = AgentDiscoveryClient::new(identity.keys.clone()).await?;
-    client.connect(&relays).await?;
+    let mut heartbeat = Heartbeat::new(&card_id, status);
+    if let Some(load) = args.load {
+        heartbeat = heartbeat.load(load);
+    }
+    if let Some(queue) = args.queue {
+        heartbeat = heartbeat.queue_depth(queue);
+    }
+    if let Some(capacity) = args.capacity {
+        heartbeat = heartbeat.capacity(capacity);
+    }
+    if let Some(version) = args.agent_version {
+        heartbeat = heartbeat.version(version);
+    }
+    if let Some(uptime) = args.uptime {
+        heartbeat = heartbeat.uptime(uptime);
+    }
+    if let Some(message) = args.message {
+        heartbeat = heartbeat.message(message);
+    }
+    if let Some(secs) = args.return_in {
+        heartbeat = heartbeat.return_at(Timestamp::from(Timestamp::now().as_u64() + secs));
+    }
+    for entry in &args.capability_status {
+        let (capability_id, cap_status) = entry
+            .split_once('=')
+            .context("Capability status must be in format 'capability=status'")?;
+        heartbeat = heartbeat.capability_status(capability_id, cap_status.parse()?);
+    }
 
-    let event_id = client.send_heartbeat(&card_id, status).await?;
+    let client = connection.open(&relays).await?;
+
+    let event_id = client.publish_heartbeat(&heartbeat).await?;
 
     client.disconnect().await?;
 
@@ -383,22 +1192,97 @@ async fn cmd_heartbeat(
 }
 
 async fn cmd_discover(
-    relays: Vec<String>,
+    args: DiscoverArgs,
+    connection: &Connection,
     default_relays: &[String],
-    capabilities: Vec<String>,
-    limit: usize,
     json_output: bool,
 ) -> Result<()> {
-    let keys = Keys::generate(); // Anonymous for read-only
-    let relays = get_relays(relays, default_relays);
+    let relays = get_relays(args.relay, default_relays);
 
-    let client = AgentDiscoveryClient::new(keys).await?;
-    client.connect(&relays).await?;
+    let source = connection.source(&relays).await?;
 
-    let cap_refs: Vec<&str> = capabilities.iter().map(|s| s.as_str()).collect();
-    let agents = client.discover_agents(limit, None, &cap_refs).await?;
+    let requirements = args
+        .capability
+        .iter()
+        .map(|c| c.parse())
+        .collect::<Result<Vec<CapabilityRequirement>, _>>()?;
 
-    client.disconnect().await?;
+    let filter = DiscoveryFilter {
+        capabilities: args.capability.clone(),
+        languages: args.language.clone(),
+        ..DiscoveryFilter::new(args.limit).min_pow(args.min_pow)
+    };
+    let agents = source.discover_agents(&filter).await?;
+    let mut agents = resolve_successors(&source, agents).await?;
+
+    let heartbeats = if args.online {
+        let mut authors: Vec<PublicKey> = agents.iter().map(|(_, event)| event.pubkey).collect();
+        authors.sort();
+        authors.dedup();
+        source.get_heartbeats_by_authors(&authors).await?
+    } else {
+        Vec::new()
+    };
+
+    source.disconnect().await?;
+
+    // Sunset agents no longer serve requests
+    agents.retain(|(card, _)| !card.deprecation.as_ref().is_some_and(Deprecation::is_sunset));
+
+    // Discovery matches any requirement; the CLI wants all of them
+    agents.retain(|(card, _)| {
+        requirements.iter().all(|req| req.is_satisfied_by(card))
+            && card.supports_io(args.accepts.as_deref(), args.produces.as_deref())
+    });
+
+    agents.retain(|(card, _)| {
+        args.language.iter().all(|lang| card.speaks(lang))
+            && args.modality.iter().all(|m| card.supports_modality(m))
+            && args
+                .min_context
+                .is_none_or(|min| card.context_window.is_some_and(|tokens| tokens >= min))
+    });
+
+    let regions: Vec<&str> = args.region.iter().map(|r| r.as_str()).collect();
+    agents.retain(|(card, _)| card.policy.satisfies(args.require_retention, &regions));
+
+    if let Some(max_sats) = args.max_price {
+        let max_msats = max_sats.saturating_mul(1000);
+        agents.retain(|(card, _)| {
+            if requirements.is_empty() {
+                card.is_affordable(max_msats, None)
+            } else {
+                requirements
+                    .iter()
+                    .all(|req| card.is_affordable(max_msats, Some(&req.id)))
+            }
+        });
+    }
+
+    if args.online {
+        let now = Timestamp::now().as_u64();
+        agents.retain(|(card, event)| {
+            let latest = heartbeats
+                .iter()
+                .filter(|(hb, hb_event)| {
+                    hb_event.pubkey == event.pubkey && hb.service_card_id == card.id
+                })
+                .max_by_key(|(_, hb_event)| hb_event.created_at);
+            let Some((hb, hb_event)) = latest else {
+                return false;
+            };
+            if now.saturating_sub(hb_event.created_at.as_u64()) >= ONLINE_WINDOW_SECS {
+                return false;
+            }
+            if requirements.is_empty() {
+                hb.status.is_available()
+            } else {
+                requirements
+                    .iter()
+                    .all(|req| hb.is_available_for(card, &req.id))
+            }
+        });
+    }
 
     if json_output {
         let output: Vec<_> = agents
@@ -410,6 +1294,14 @@ async fn cmd_discover(
                     "about": card.about,
                     "capabilities": card.capabilities,
                     "protocols": card.protocols,
+                    "pricing": card.pricing,
+                    "payment_methods": card.payment_methods,
+                    "model": card.model,
+                    "modalities": card.modalities,
+                    "context_window": card.context_window,
+                    "languages": card.languages,
+                    "policy": card.policy,
+                    "deprecation": card.deprecation,
                     "pubkey": event.pubkey.to_hex(),
                 })
             })
@@ -418,15 +1310,22 @@ async fn cmd_discover(
     } else {
         println!("Found {} agent(s):\n", agents.len());
         for (card, event) in &agents {
-            println!("  {} ({})", card.name, card.id);
+            let deprecated = if card.is_deprecated() { " [deprecated]" } else { "" };
+            println!("  {} ({}){deprecated}", card.name, card.id);
             println!("    Author: {}...", &event.pubkey.to_hex()[..16]);
             if !card.about.is_empty() {
                 println!("    About:  {}", card.about);
             }
             if !card.capabilities.is_empty() {
-                let caps: Vec<&str> = card.capabilities.iter().map(|c| c.id.as_str()).collect();
+                let caps: Vec<String> = card.capabilities.iter().map(|c| c.to_string()).collect();
                 println!("    Capabilities: {}", caps.join(", "));
             }
+            if let Some(pricing) = &card.pricing {
+                println!("    Price:  {}", format_price(pricing));
+            }
+            if !card.languages.is_empty() {
+                println!("    Languages: {}", card.languages.join(", "));
+            }
             println!();
         }
     }
@@ -434,38 +1333,101 @@ async fn cmd_discover(
     Ok(())
 }
 
+/// Replace deprecated cards with the newest card in their successor chain.
+///
+/// Broken chains are reported on stderr and leave the last card reached.
+async fn resolve_successors(
+    source: &Source,
+    agents: Vec<(ServiceCard, Event)>,
+) -> Result<Vec<(ServiceCard, Event)>> {
+    let mut resolved: Vec<(ServiceCard, Event)> = Vec::with_capacity(agents.len());
+    for (card, event) in agents {
+        let current = if card.successor().is_some() {
+            let old = card.coordinate(event.pubkey);
+            let SuccessorChain { mut cards, end } = source.follow_successors(card, event).await?;
+            if end != ChainEnd::Resolved {
+                eprintln!("warning: {old}: {end}");
+            }
+            cards.pop().expect("chain starts with a card")
+        } else {
+            (card, event)
+        };
+
+        // The successor may also have matched the query on its own
+        let duplicate = resolved
+            .iter()
+            .any(|(card, event)| card.id == current.0.id && event.pubkey == current.1.pubkey);
+        if !duplicate {
+            resolved.push(current);
+        }
+    }
+    Ok(resolved)
+}
+
+/// Cards of a lookup target: the one addressed, or all of the agent's.
+async fn find_cards(source: &Source, target: &Target) -> Result<Vec<(ServiceCard, Event)>> {
+    source.connect_outbox(&[target.pubkey], &[]).await?;
+    Ok(match target.coordinate() {
+        Some(coordinate) => source.get_service_card(&coordinate).await?.into_iter().collect(),
+        None => source.get_service_cards(target.pubkey).await?,
+    })
+}
+
 async fn cmd_lookup(
-    pubkey: String,
-    relays: Vec<String>,
+    args: LookupArgs,
+    connection: &Connection,
     default_relays: &[String],
     json_output: bool,
 ) -> Result<()> {
-    let keys = Keys::generate();
-    let relays = get_relays(relays, default_relays);
-
-    let target_pk = if pubkey.starts_with("npub") {
-        nostr::nips::nip19::FromBech32::from_bech32(&pubkey)?
-    } else {
-        nostr::PublicKey::from_hex(&pubkey)?
-    };
-
-    let client = AgentDiscoveryClient::new(keys).await?;
-    client.connect(&relays).await?;
+    let pubkey = args.pubkey;
+    let mut target = parse_target(&pubkey)?;
+    if args.id.is_some() {
+        target.card_id = args.id;
+    }
+    let relays = with_hints(get_relays(args.relay, default_relays), &target.relays);
 
-    let agents = client.discover_agents(10, Some(target_pk), &[]).await?;
+    let mut source = connection.source(&relays).await?;
+    let mut cards = find_cards(&source, &target).await?;
+    if cards.is_empty() {
+        source = connection.on_miss(source, &relays).await?;
+        cards = find_cards(&source, &target).await?;
+    }
 
-    client.disconnect().await?;
+    if cards.len() > 1 {
+        let heartbeats = source.get_heartbeats_by_authors(&[target.pubkey]).await?;
+        source.disconnect().await?;
+        return print_card_list(&pubkey, &cards, &heartbeats, json_output);
+    }
 
-    if agents.is_empty() {
+    let Some((card, event)) = cards.pop() else {
+        source.disconnect().await?;
         if json_output {
             println!("null");
         } else {
             println!("No service card found for {}", pubkey);
         }
         return Ok(());
-    }
+    };
 
-    let (card, event) = &agents[0];
+    // Follow a deprecated card to its replacement
+    let mut superseded = Vec::new();
+    let (card, event) = if !args.no_follow && card.successor().is_some() {
+        let SuccessorChain { mut cards, end } = source.follow_successors(card, event).await?;
+        let current = cards.pop().expect("chain starts with a card");
+        for (old, old_event) in &cards {
+            let coordinate = old.coordinate(old_event.pubkey);
+            eprintln!("note: {coordinate} is deprecated, following its successor");
+            superseded.push(coordinate.to_string());
+        }
+        if end != ChainEnd::Resolved {
+            eprintln!("warning: {end}");
+        }
+        current
+    } else {
+        (card, event)
+    };
+
+    source.disconnect().await?;
 
     if json_output {
         let output = serde_json::json!({
@@ -474,6 +1436,18 @@ async fn cmd_lookup(
             "about": card.about,
             "capabilities": card.capabilities,
             "protocols": card.protocols,
+            "pricing": card.pricing,
+            "payment_methods": card.payment_methods,
+            "schedule": card.schedule,
+            "maintenance": card.maintenance,
+            "model": card.model,
+            "modalities": card.modalities,
+            "context_window": card.context_window,
+            "languages": card.languages,
+            "policy": card.policy,
+            "deprecation": card.deprecation,
+            "content": card.content,
+            "superseded": superseded,
             "pubkey": event.pubkey.to_hex(),
         });
         println!("{}", serde_json::to_string_pretty(&output)?);
@@ -483,96 +1457,585 @@ async fn cmd_lookup(
         if !card.about.is_empty() {
             println!("  About:  {}", card.about);
         }
+        if let Some(deprecation) = &card.deprecation {
+            let reason = deprecation.reason.as_deref().unwrap_or("no reason given");
+            println!("  ⚠ Deprecated: {reason}");
+            if let Some(sunset) = deprecation.sunset {
+                let state = if deprecation.is_sunset() { "was" } else { "is" };
+                println!("    Sunset {state} at {sunset}");
+            }
+            if let Some(successor) = &deprecation.successor {
+                println!("    Successor: {successor}");
+            }
+        }
+        if let Some(model) = &card.model {
+            println!("  Model:  {model}");
+        }
+        if !card.modalities.is_empty() {
+            println!("  Modalities: {}", card.modalities.join(", "));
+        }
+        if let Some(tokens) = card.context_window {
+            println!("  Context:    {tokens} tokens");
+        }
+        if !card.languages.is_empty() {
+            println!("  Languages:  {}", card.languages.join(", "));
+        }
         println!("\n  Capabilities:");
         for cap in &card.capabilities {
-            println!("    - {}: {}", cap.id, cap.description);
+            println!("    - {cap}: {}", cap.description);
+            if !cap.input.is_empty() || !cap.output.is_empty() {
+                println!("        {} -> {}", or_any(&cap.input), or_any(&cap.output));
+            }
+            if let Some(schema) = &cap.schema {
+                println!("        schema: {schema}");
+            }
+            if let Some(max) = cap.max_input_bytes {
+                println!("        max input: {max} bytes");
+            }
+            if let Some(pricing) = &cap.pricing {
+                println!("        price: {}", format_price(pricing));
+            }
+        }
+        if card.pricing.is_some() || !card.payment_methods.is_empty() {
+            println!("\n  Pricing:");
+            if let Some(pricing) = &card.pricing {
+                println!("    Default: {}", format_price(pricing));
+            }
+            for method in &card.payment_methods {
+                match method {
+                    PaymentMethod::Lightning { address } => println!("    - lightning: {address}"),
+                    PaymentMethod::Zap => println!("    - zaps (NIP-57)"),
+                    PaymentMethod::Bolt12 { offer } => println!("    - bolt12: {offer}"),
+                }
+            }
+        }
+        if !card.policy.is_empty() {
+            let policy = &card.policy;
+            println!("\n  Policy:");
+            if let Some(url) = &policy.terms {
+                println!("    Terms:     {url}");
+            }
+            if let Some(url) = &policy.privacy {
+                println!("    Privacy:   {url}");
+            }
+            if let Some(retention) = &policy.retention {
+                println!("    Retention: {retention}");
+            }
+            if let Some(training) = &policy.training {
+                println!("    Training:  {training}");
+            }
+            if !policy.regions.is_empty() {
+                println!("    Regions:   {}", policy.regions.join(", "));
+            }
+        }
+        if !card.schedule.is_empty() || !card.maintenance.is_empty() {
+            println!("\n  Availability:");
+            for window in &card.schedule {
+                println!("    - {window}");
+            }
+            for window in &card.maintenance {
+                let reason = window.reason.as_deref().unwrap_or("planned maintenance");
+                println!("    - {reason}: {} to {}", window.start, window.end);
+            }
         }
         println!("\n  Protocols:");
         for proto in &card.protocols {
             println!("    - {}: {}", proto.id(), proto.endpoint());
         }
+        if !card.content.is_empty() {
+            if args.full {
+                println!("\n  Documentation:\n");
+                for line in card.content.lines() {
+                    println!("    {line}");
+                }
+            } else {
+                let bytes = card.content.len();
+                println!("\n  Documentation: {bytes} bytes (use --full to show)");
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Cards of all status targets, in one request for all agents.
+async fn find_agent_cards(
+    source: &Source,
+    targets: &[Target],
+    pubkeys: &[PublicKey],
+) -> Result<Vec<(ServiceCard, Event)>> {
+    source.connect_outbox(pubkeys, &[]).await?;
+    let mut cards = source.get_service_cards_by_authors(pubkeys).await?;
+    cards.retain(|(card, event)| targets.iter().any(|t| t.wants(event.pubkey, &card.id)));
+    Ok(cards)
+}
+
 async fn cmd_status(
-    pubkey: String,
-    relays: Vec<String>,
+    args: StatusArgs,
+    connection: &Connection,
     default_relays: &[String],
     json_output: bool,
-) -> Result<()> {
-    let keys = Keys::generate();
-    let relays = get_relays(relays, default_relays);
+) -> Result<ExitCode> {
+    let mut inputs = args.agents;
+    if let Some(path) = &args.file {
+        inputs.extend(read_agent_list(path)?);
+    }
+    if inputs.is_empty() {
+        anyhow::bail!("Give at least one agent, or a file listing them with --file");
+    }
+    // A file is a batch even if it lists a single agent
+    let batch = args.file.is_some() || inputs.len() > 1;
+
+    let targets = inputs
+        .iter()
+        .map(|input| parse_target(input))
+        .collect::<Result<Vec<Target>>>()?;
+    let hints: Vec<String> = targets.iter().flat_map(|t| t.relays.clone()).collect();
+    let relays = with_hints(get_relays(args.relay, default_relays), &hints);
+    let mut pubkeys: Vec<PublicKey> = targets.iter().map(|t| t.pubkey).collect();
+    pubkeys.sort();
+    pubkeys.dedup();
+
+    let mut source = connection.source(&relays).await?;
+    let mut cards = find_agent_cards(&source, &targets, &pubkeys).await?;
+    let missing = targets
+        .iter()
+        .any(|t| !cards.iter().any(|(card, event)| t.wants(event.pubkey, &card.id)));
+    if missing {
+        source = connection.on_miss(source, &relays).await?;
+        cards = find_agent_cards(&source, &targets, &pubkeys).await?;
+    }
+
+    let seen: Vec<ServiceCard> = cards.iter().map(|(card, _)| card.clone()).collect();
+    source.connect_outbox(&[], &seen).await?;
+
+    let heartbeats = source.get_heartbeats_by_authors(&pubkeys).await?;
 
-    let target_pk = if pubkey.starts_with("npub") {
-        nostr::nips::nip19::FromBech32::from_bech32(&pubkey)?
+    source.disconnect().await?;
+
+    let now = Timestamp::now();
+    let agents: Vec<(&str, Vec<CardPresence>)> = inputs
+        .iter()
+        .zip(&targets)
+        .map(|(input, target)| {
+            let statuses = cards
+                .iter()
+                .filter(|(card, event)| target.wants(event.pubkey, &card.id))
+                .map(|(card, event)| {
+                    let latest = latest_heartbeat(&heartbeats, event.pubkey, &card.id);
+                    CardPresence::evaluate(card, latest, now)
+                })
+                .collect();
+            (input.as_str(), statuses)
+        })
+        .collect();
+
+    let online = agents
+        .iter()
+        .filter(|(_, statuses)| statuses.iter().any(|s| s.presence.is_online()))
+        .count();
+
+    if !batch {
+        print_agent_status(&agents[0].1, json_output)?;
+    } else if json_output {
+        let output: Vec<_> = agents
+            .iter()
+            .zip(&targets)
+            .map(|((input, statuses), target)| {
+                serde_json::json!({
+                    "agent": input,
+                    "pubkey": target.pubkey.to_hex(),
+                    "online": statuses.iter().any(|s| s.presence.is_online()),
+                    "cards": statuses.iter().map(CardPresence::to_json).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
-        nostr::PublicKey::from_hex(&pubkey)?
-    };
+        println!("   {:<24} {:<20} {:<28} LAST SEEN", "AGENT", "CARD", "PRESENCE");
+        for (input, statuses) in &agents {
+            if statuses.is_empty() {
+                println!("⚫ {:<24} {:<20} {:<28} -", truncate(input, 24), "-", "no service card");
+            }
+            for status in statuses {
+                let seen = match status.age_secs {
+                    Some(age) => format!("{} ago", format_duration(age)),
+                    None => "never".to_string(),
+                };
+                println!(
+                    "{} {:<24} {:<20} {:<28} {}",
+                    status.emoji(),
+                    truncate(&status.card.name, 24),
+                    truncate(&status.card.id, 20),
+                    truncate(&status.presence.to_string(), 28),
+                    seen
+                );
+            }
+        }
+        println!("\n{online}/{} agents online", agents.len());
+    }
 
-    let client = AgentDiscoveryClient::new(keys).await?;
-    client.connect(&relays).await?;
+    // Let scripts and health checks tell the outcome apart from errors (1)
+    match online {
+        n if n == agents.len() => Ok(ExitCode::SUCCESS),
+        0 => Ok(ExitCode::from(EXIT_NONE_ONLINE)),
+        _ => Ok(ExitCode::from(EXIT_SOME_ONLINE)),
+    }
+}
 
-    // First find their service card to get the ID
-    let agents = client.discover_agents(1, Some(target_pk), &[]).await?;
+/// Status of one agent: details for a single card, a list for several.
+fn print_agent_status(statuses: &[CardPresence], json_output: bool) -> Result<()> {
+    let now = Timestamp::now();
+    match statuses {
+        [] if json_output => println!(r#"{{"online": false, "reason": "no service card"}}"#),
+        [] => println!("Agent not found (no service card)"),
+        [status] if json_output => println!("{}", serde_json::to_string_pretty(&status.to_json())?),
+        [status] => status.print(now),
+        statuses if json_output => {
+            let output: Vec<_> = statuses.iter().map(CardPresence::to_json).collect();
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        statuses => {
+            println!("{} service cards:\n", statuses.len());
+            for status in statuses {
+                println!(
+                    "{} {} ({}) - {} ({})",
+                    status.emoji(),
+                    status.card.name,
+                    status.card.id,
+                    status.presence,
+                    status.last_seen()
+                );
+            }
+        }
+    }
+    Ok(())
+}
 
-    if agents.is_empty() {
-        client.disconnect().await?;
-        if json_output {
-            println!(r#"{{"online": false, "reason": "no service card"}}"#);
-        } else {
-            println!("Agent not found (no service card)");
+/// Agents listed in a file, one per line; blank lines and `#` comments are skipped.
+fn read_agent_list(path: &PathBuf) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read agent list: {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Cut a table cell to `width` characters.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let cut: String = s.chars().take(width - 1).collect();
+        format!("{cut}…")
+    }
+}
+
+/// Newest heartbeat of one card.
+fn latest_heartbeat<'a>(
+    heartbeats: &'a [(Heartbeat, Event)],
+    author: PublicKey,
+    card_id: &str,
+) -> Option<&'a (Heartbeat, Event)> {
+    heartbeats
+        .iter()
+        .filter(|(hb, event)| event.pubkey == author && hb.service_card_id == card_id)
+        .max_by_key(|(_, event)| event.created_at)
+}
+
+/// A card's presence, judged from its newest heartbeat
+struct CardPresence<'a> {
+    card: &'a ServiceCard,
+    heartbeat: Option<&'a Heartbeat>,
+    age_secs: Option<u64>,
+    presence: Presence,
+    available: bool,
+    next_available: Option<Timestamp>,
+}
+
+impl<'a> CardPresence<'a> {
+    fn evaluate(
+        card: &'a ServiceCard,
+        latest: Option<&'a (Heartbeat, Event)>,
+        now: Timestamp,
+    ) -> Self {
+        let hb = latest.map(|(hb, _)| hb);
+        let age_secs =
+            latest.map(|(_, event)| now.as_u64().saturating_sub(event.created_at.as_u64()));
+
+        let presence = Presence::evaluate(card, latest.map(|(hb, e)| (hb, e.created_at)), now);
+        let available = matches!(&presence, Presence::Online { status } if status.is_available());
+        let next_available = match &presence {
+            Presence::Maintenance { until } => *until,
+            Presence::OffHours { next_available } => *next_available,
+            _ => hb.and_then(|hb| hb.return_at),
+        };
+
+        CardPresence {
+            card,
+            heartbeat: hb,
+            age_secs,
+            presence,
+            available,
+            next_available,
         }
-        return Ok(());
     }
 
-    let (card, _) = &agents[0];
+    fn emoji(&self) -> &'static str {
+        match &self.presence {
+            Presence::Online { .. } if self.available => "🟢",
+            Presence::Online { .. } => "🟡",
+            Presence::Maintenance { .. } | Presence::OffHours { .. } => "⚪",
+            Presence::Offline => "⚫",
+            Presence::UnexpectedlyOffline => "🔴",
+        }
+    }
 
-    // Now get their heartbeats
-    let heartbeats = client.get_heartbeats(target_pk, &card.id, 1).await?;
+    fn last_seen(&self) -> String {
+        match self.age_secs {
+            Some(age) => format!("last seen: {} ago", format_duration(age)),
+            None => "no heartbeat".to_string(),
+        }
+    }
 
-    client.disconnect().await?;
+    fn to_json(&self) -> serde_json::Value {
+        let hb = self.heartbeat;
+        serde_json::json!({
+            "online": self.presence.is_online(),
+            "available": self.available,
+            "presence": self.presence,
+            "next_available": self.next_available.map(|t| t.as_u64()),
+            "status": hb.map(|hb| hb.status.to_string()),
+            "last_seen_secs": self.age_secs,
+            "id": self.card.id,
+            "name": self.card.name,
+            "load": hb.and_then(|hb| hb.load),
+            "queue_depth": hb.and_then(|hb| hb.queue_depth),
+            "capacity": hb.and_then(|hb| hb.capacity),
+            "version": hb.and_then(|hb| hb.version.as_ref()),
+            "uptime_secs": hb.and_then(|hb| hb.uptime),
+            "message": hb.and_then(|hb| hb.message.as_ref()),
+            "capabilities": hb.map(|hb| &hb.capabilities),
+        })
+    }
 
-    if heartbeats.is_empty() {
-        if json_output {
-            println!(r#"{{"online": false, "reason": "no heartbeat"}}"#);
-        } else {
-            println!("{} - No heartbeat found", card.name);
+    fn print(&self, now: Timestamp) {
+        let card = self.card;
+        println!("{} {} - {} ({})", self.emoji(), card.name, self.presence, self.last_seen());
+        if let Some(hb) = self.heartbeat {
+            if let Some(message) = &hb.message {
+                println!("  Message:  {message}");
+            }
+            for (capability_id, cap_status) in &hb.capabilities {
+                println!("  {capability_id}: {cap_status}");
+            }
+            if let Some(load) = hb.load {
+                println!("  Load:     {load}%");
+            }
+            if hb.queue_depth.is_some() || hb.capacity.is_some() {
+                let queue = hb.queue_depth.map_or("?".to_string(), |q| q.to_string());
+                let capacity = hb.capacity.map_or("?".to_string(), |c| c.to_string());
+                println!("  Queue:    {queue} waiting, {capacity} concurrent");
+            }
+            if let Some(version) = &hb.version {
+                println!("  Version:  {version}");
+            }
+            if let Some(uptime) = hb.uptime {
+                println!("  Uptime:   {}h {}m", uptime / 3600, uptime % 3600 / 60);
+            }
+        }
+        if let Some(at) = self.next_available {
+            let secs = at.as_u64().saturating_sub(now.as_u64());
+            println!("  Back in:  ~{}", format_duration(secs));
         }
+        if matches!(self.presence, Presence::OffHours { .. }) {
+            for window in &card.schedule {
+                println!("  Hours:    {window}");
+            }
+        }
+    }
+}
+
+/// List an agent's cards with their last update and presence.
+fn print_card_list(
+    pubkey: &str,
+    cards: &[(ServiceCard, Event)],
+    heartbeats: &[(Heartbeat, Event)],
+    json_output: bool,
+) -> Result<()> {
+    let now = Timestamp::now();
+
+    if json_output {
+        let output: Vec<_> = cards
+            .iter()
+            .map(|(card, event)| {
+                let latest = latest_heartbeat(heartbeats, event.pubkey, &card.id);
+                let status = CardPresence::evaluate(card, latest, now);
+                serde_json::json!({
+                    "id": card.id,
+                    "name": card.name,
+                    "about": card.about,
+                    "updated_at": event.created_at.as_u64(),
+                    "deprecated": card.is_deprecated(),
+                    "online": status.presence.is_online(),
+                    "presence": status.presence,
+                    "last_seen_secs": status.age_secs,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&output)?);
         return Ok(());
     }
 
-    let (hb, event) = &heartbeats[0];
-    let created_at = event.created_at.as_u64();
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)?
-        .as_secs();
-    let age_secs = now.saturating_sub(created_at);
-    let online = age_secs < 900; // Consider online if heartbeat < 15 min old
+    println!("{} service cards for {}:\n", cards.len(), pubkey);
+    for (card, event) in cards {
+        let latest = latest_heartbeat(heartbeats, event.pubkey, &card.id);
+        let status = CardPresence::evaluate(card, latest, now);
+        let updated = now.as_u64().saturating_sub(event.created_at.as_u64());
+        let deprecated = if card.is_deprecated() { " [deprecated]" } else { "" };
+
+        println!("{} {} ({}){}", status.emoji(), card.name, card.id, deprecated);
+        println!(
+            "   Updated {} ago · {} ({})",
+            format_duration(updated),
+            status.presence,
+            status.last_seen()
+        );
+    }
+    println!("\nShow one with: agent-bridge lookup {pubkey} --id <card id>");
+
+    Ok(())
+}
 
+/// Render a duration for humans, e.g. "45s", "12m", "3h 5m" or "2d 4h".
+fn format_duration(secs: u64) -> String {
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86_400 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}d {}h", secs / 86_400, secs % 86_400 / 3600)
+    }
+}
+
+fn or_any(types: &[String]) -> String {
+    if types.is_empty() {
+        "any".to_string()
+    } else {
+        types.join(", ")
+    }
+}
+
+fn cmd_capabilities_list(json_output: bool) -> Result<()> {
     if json_output {
-        let output = serde_json::json!({
-            "online": online,
-            "status": hb.status.to_string(),
-            "last_seen_secs": age_secs,
-            "name": card.name,
-        });
+        let output: Vec<_> = KNOWN_CAPABILITIES
+            .iter()
+            .map(|known| {
+                serde_json::json!({
+                    "id": known.id,
+                    "description": known.description,
+                    "aliases": known.aliases,
+                    "category": known.category,
+                })
+            })
+            .collect();
         println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
-        let status_emoji = if online { "🟢" } else { "⚫" };
-        let age_str = if age_secs < 60 {
-            format!("{}s ago", age_secs)
-        } else if age_secs < 3600 {
-            format!("{}m ago", age_secs / 60)
-        } else {
-            format!("{}h ago", age_secs / 3600)
+        println!("Well-known capabilities:\n");
+        for known in KNOWN_CAPABILITIES {
+            println!("  {:<22} {}", known.id, known.description);
+            if !known.aliases.is_empty() {
+                println!("  {:<22} aliases: {}", "", known.aliases.join(", "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_relays_info(
+    relays: Vec<String>,
+    connection: &Connection,
+    default_relays: &[String],
+    json_output: bool,
+) -> Result<()> {
+    let relays = get_relays(relays, default_relays);
+
+    let client = connection.open(&relays).await?;
+    let info = client.relay_info();
+    client.disconnect().await?;
+
+    let find = |relay: &str| {
+        info.iter()
+            .find(|(url, _)| url.as_str().trim_end_matches('/') == relay.trim_end_matches('/'))
+            .map(|(_, doc)| doc)
+    };
+
+    if json_output {
+        let output: Vec<_> = relays
+            .iter()
+            .map(|relay| {
+                serde_json::json!({
+                    "relay": relay,
+                    "info": find(relay),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    for (i, relay) in relays.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{relay}");
+        let Some(doc) = find(relay) else {
+            println!("  No NIP-11 document");
+            continue;
         };
 
-        println!(
-            "{} {} - {} (last seen: {})",
-            status_emoji, card.name, hb.status, age_str
-        );
+        if let Some(name) = &doc.name {
+            println!("  Name:        {name}");
+        }
+        if let Some(description) = &doc.description {
+            println!("  Description: {description}");
+        }
+        if let Some(software) = &doc.software {
+            match &doc.version {
+                Some(version) => println!("  Software:    {software} {version}"),
+                None => println!("  Software:    {software}"),
+            }
+        }
+        if let Some(nips) = &doc.supported_nips {
+            let nips: Vec<String> = nips.iter().map(|n| n.to_string()).collect();
+            println!("  NIPs:        {}", nips.join(", "));
+        }
+
+        let Some(limitation) = &doc.limitation else {
+            continue;
+        };
+        println!("  Limitations:");
+        let flag = |value: Option<bool>| if value == Some(true) { "yes" } else { "no" };
+        println!("    Auth required:    {}", flag(limitation.auth_required));
+        println!("    Payment required: {}", flag(limitation.payment_required));
+        if let Some(bits) = limitation.min_pow_difficulty {
+            println!("    Min PoW:          {bits} bits");
+        }
+        if let Some(bytes) = limitation.max_message_length {
+            println!("    Max message:      {bytes} bytes");
+        }
+        if let Some(tags) = limitation.max_event_tags {
+            println!("    Max tags:         {tags}");
+        }
+        if let Some(chars) = limitation.max_content_length {
+            println!("    Max content:      {chars} characters");
+        }
     }
 
     Ok(())
@@ -582,20 +2045,47 @@ fn cmd_help(topic: Option<String>) {
     match topic.as_deref() {
         Some("protocols") => {
             println!("Supported protocols:\n");
-            println!("  dm:<relays>      Nostr DMs (NIP-04/17)");
-            println!("  dvm:<relays>     NIP-90 Data Vending Machine");
-            println!("  a2a:<url>        Google A2A agent card URL");
-            println!("  mcp:<url>        Model Context Protocol endpoint");
-            println!("  http:<url>       REST API endpoint");
+            println!("  dm:<relays>        Legacy Nostr DMs (NIP-04)");
+            println!("  nip17:<relays>     Private gift-wrapped DMs (NIP-17)");
+            println!("  dvm:<relays>       NIP-90 Data Vending Machine");
+            println!("  a2a:<url>          Google A2A agent card URL");
+            println!("  mcp:<url>          Model Context Protocol endpoint");
+            println!("  http:<url>         REST API endpoint");
+            println!("  openapi:<url>      OpenAPI document URL");
+            println!("  websocket:<url>    Raw WebSocket endpoint");
+            println!("  grpc:<url>         gRPC service endpoint");
             println!("\nExample:");
             println!("  --protocol a2a:https://example.com/.well-known/agent.json");
         }
         Some("capabilities") => {
             println!("Capabilities are in format 'id:description'\n");
+            println!("Ids are dotted namespaces; discovering a prefix finds everything below it.\n");
             println!("Examples:");
-            println!("  --capability transcription:\"Convert audio to text\"");
-            println!("  --capability summarization:\"Summarize documents\"");
-            println!("  --capability coding:\"Write and review code\"");
+            println!("  --capability audio.transcription:\"Convert audio to text\"");
+            println!("  --capability text.summarization:\"Summarize documents\"");
+            println!("  --capability code.review.rust:\"Review Rust code\"");
+            println!();
+            println!("  discover --capability audio     # matches audio.transcription");
+            println!();
+            println!("Versions follow semver:");
+            println!("  --capability text.translation@2.1.0:\"Translate text\"");
+            println!("  discover --capability text.translation@^2");
+            println!();
+            println!("Prefer well-known ids; list them with 'agent-bridge capabilities list'.");
+            println!("Aliases such as 'summarize' are expanded automatically when discovering.");
+            println!();
+            println!("Discover by input/output type:");
+            println!("  discover --accepts audio/wav --produces text/plain");
+            println!();
+            println!("Discover by language, modality or context size:");
+            println!("  discover --language de --modality image --min-context 32000");
+            println!();
+            println!("Exclude agents without acceptable data-handling terms:");
+            println!("  discover --require-retention --region EU --region CH");
+            println!();
+            println!("Skip spam cards by requiring NIP-13 proof of work:");
+            println!("  publish --pow 20");
+            println!("  discover --min-pow 16");
         }
         Some("relays") => {
             println!("Relay configuration:");
@@ -603,6 +2093,37 @@ fn cmd_help(topic: Option<String>) {
             println!("  Uses relays from OpenClaw config (channels.nostr.relays)");
             println!("  Override with --relay (can be repeated):");
             println!("    --relay wss://relay.example.com --relay wss://other.relay");
+            println!();
+            println!("  Relay NIP-11 documents are fetched on connect. Publishing skips");
+            println!("  relays whose limitations the event doesn't meet (auth, payment,");
+            println!("  proof of work, size). Show them with 'agent-bridge relays info'.");
+            println!();
+            println!("  Private relays that require NIP-42 AUTH (repeatable):");
+            println!("    --auth-relay wss://private.relay.example");
+            println!("  AUTH is signed with the configured key, also for read-only commands.");
+            println!();
+            println!("  lookup and status also query the relays the agent lists in its");
+            println!("  NIP-65 relay list (kind 10002). Add yours when publishing:");
+            println!("    publish --relay-list");
+            println!("  It merges into your existing list; --force creates one if none is found.");
+            println!();
+            println!("  Relay hints in an nprofile or naddr are queried too:");
+            println!("    lookup nostr:naddr1...");
+        }
+        Some("cache") => {
+            println!("Local discovery cache:");
+            println!();
+            println!("  discover, lookup and status answer from a local cache of cards");
+            println!("  and heartbeats. It is updated from the relays when older than");
+            println!("  --max-age seconds (default 60), fetching only newer events.");
+            println!();
+            println!("    discover --capability audio --max-age 600");
+            println!("    status --file agents.txt --offline   # never touch the network");
+            println!("    lookup npub1... --no-cache           # always ask relays");
+            println!();
+            println!("  Agents missing from the cache are looked up on relays unless --offline.");
+            println!("  Default location: ~/.cache/agent-bridge/discovery.sqlite");
+            println!("  Override with --cache-file or AGENT_BRIDGE_CACHE env var");
         }
         Some("config") => {
             println!("Configuration:");
@@ -627,13 +2148,17 @@ fn cmd_help(topic: Option<String>) {
             println!("  agent-bridge help protocols     Protocol formats");
             println!("  agent-bridge help capabilities  Capability formats");
             println!("  agent-bridge help relays        Relay configuration");
+            println!("  agent-bridge help cache         Local discovery cache");
             println!("\nCommands:");
             println!("  whoami     Show your identity (from OpenClaw config)");
             println!("  publish    Publish your service card");
             println!("  heartbeat  Send a status heartbeat");
             println!("  discover   Find agents");
             println!("  lookup     Look up a specific agent");
-            println!("  status     Check if an agent is online");
+            println!("  status     Check if agents are online");
+            println!("             exit 0: all online, 2: some, 3: none, 1: error");
+            println!("  capabilities list  Show well-known capability ids");
+            println!("  relays info        Show relay NIP-11 documents and limitations");
         }
     }
 }
@@ -643,7 +2168,7 @@ fn cmd_help(topic: Option<String>) {
 // ============================================================================
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
     let config_path = get_config_path(cli.config)?;
 
@@ -651,70 +2176,84 @@ async fn main() -> Result<()> {
     match &cli.command {
         Commands::Help { topic } => {
             cmd_help(topic.clone());
-            return Ok(());
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Capabilities { command } => {
+            return match command {
+                CapabilitiesCommand::List => cmd_capabilities_list(cli.json),
+            }
+            .map(|()| ExitCode::SUCCESS);
         }
-        Commands::Discover {
-            capability,
-            limit,
-            relay,
-        } => {
+        Commands::Relays { command } => {
+            let default_relays = match load_identity(&config_path) {
+                Ok(id) => id.relays,
+                Err(_) => DEFAULT_RELAYS.iter().map(|s| s.to_string()).collect(),
+            };
+            let connection =
+                Connection::reader(&config_path, cli.auth_relay.clone(), cli.cache.clone())?;
+            return match command {
+                RelaysCommand::Info { relay } => {
+                    cmd_relays_info(relay.clone(), &connection, &default_relays, cli.json).await
+                }
+            }
+            .map(|()| ExitCode::SUCCESS);
+        }
+        Commands::Discover(args) => {
             // Try to load config for default relays, but don't require it
             let default_relays = match load_identity(&config_path) {
                 Ok(id) => id.relays,
                 Err(_) => DEFAULT_RELAYS.iter().map(|s| s.to_string()).collect(),
             };
-            return cmd_discover(relay.clone(), &default_relays, capability.clone(), *limit, cli.json).await;
+            let connection =
+                Connection::reader(&config_path, cli.auth_relay.clone(), cli.cache.clone())?;
+            return cmd_discover(args.clone(), &connection, &default_relays, cli.json)
+                .await
+                .map(|()| ExitCode::SUCCESS);
         }
-        Commands::Lookup { pubkey, relay } => {
+        Commands::Lookup(args) => {
             let default_relays = match load_identity(&config_path) {
                 Ok(id) => id.relays,
                 Err(_) => DEFAULT_RELAYS.iter().map(|s| s.to_string()).collect(),
             };
-            return cmd_lookup(pubkey.clone(), relay.clone(), &default_relays, cli.json).await;
+            let connection =
+                Connection::reader(&config_path, cli.auth_relay.clone(), cli.cache.clone())?;
+            return cmd_lookup(args.clone(), &connection, &default_relays, cli.json)
+                .await
+                .map(|()| ExitCode::SUCCESS);
         }
-        Commands::Status { pubkey, relay } => {
+        Commands::Status(args) => {
             let default_relays = match load_identity(&config_path) {
                 Ok(id) => id.relays,
                 Err(_) => DEFAULT_RELAYS.iter().map(|s| s.to_string()).collect(),
             };
-            return cmd_status(pubkey.clone(), relay.clone(), &default_relays, cli.json).await;
+            let connection =
+                Connection::reader(&config_path, cli.auth_relay.clone(), cli.cache.clone())?;
+            return cmd_status(args.clone(), &connection, &default_relays, cli.json).await;
         }
         _ => {}
     }
 
     // Commands that need identity
     let identity = load_identity(&config_path)?;
+    let connection = Connection {
+        keys: identity.keys.clone(),
+        auth_relays: cli.auth_relay,
+        cache: cli.cache,
+    };
 
-    match cli.command {
+    let result = match cli.command {
         Commands::Whoami => cmd_whoami(&identity, cli.json),
-        Commands::Publish {
-            id,
-            name,
-            about,
-            capability,
-            protocol,
-            relay,
-        } => {
-            cmd_publish(
-                &identity,
-                id,
-                name,
-                about,
-                capability,
-                protocol,
-                relay,
-                cli.json,
-            )
-            .await
-        }
-        Commands::Heartbeat {
-            status,
-            service_card_id,
-            relay,
-        } => cmd_heartbeat(&identity, status, service_card_id, relay, cli.json).await,
+        Commands::Publish(args) => cmd_publish(&identity, &connection, *args, cli.json).await,
+        Commands::Heartbeat(args) => cmd_heartbeat(&identity, &connection, args, cli.json).await,
         // Already handled above
-        Commands::Discover { .. } | Commands::Lookup { .. } | Commands::Status { .. } | Commands::Help { .. } => {
+        Commands::Discover(_)
+        | Commands::Lookup(_)
+        | Commands::Status(_)
+        | Commands::Capabilities { .. }
+        | Commands::Relays { .. }
+        | Commands::Help { .. } => {
             unreachable!()
         }
-    }
+    };
+    result.map(|()| ExitCode::SUCCESS)
 }
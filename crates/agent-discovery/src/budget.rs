@@ -0,0 +1,243 @@
+//! Event size budget
+//!
+//! Public relays reject events over their NIP-11 limits. These helpers
+//! estimate the serialized size of an event before it is signed so the
+//! problem surfaces as a clear warning instead of a generic relay error.
+
+use nostr::{Event, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Bytes a signed event adds around its tags and content: id, pubkey,
+/// signature, created_at, kind, field names and the `["EVENT", ...]` wrapper.
+const EVENT_OVERHEAD_BYTES: usize = 360;
+
+/// Relay limits as advertised in NIP-11 `limitation`.
+///
+/// `None` means the relay doesn't limit that dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayLimits {
+    /// Maximum websocket message size in bytes
+    pub max_message_length: Option<usize>,
+
+    /// Maximum number of tags per event
+    pub max_event_tags: Option<usize>,
+
+    /// Maximum content length in characters
+    pub max_content_length: Option<usize>,
+}
+
+impl RelayLimits {
+    /// No limits at all.
+    pub const UNLIMITED: RelayLimits = RelayLimits {
+        max_message_length: None,
+        max_event_tags: None,
+        max_content_length: None,
+    };
+
+    /// Tightest of two sets of limits, e.g. across all relays published to.
+    pub fn min(self, other: RelayLimits) -> RelayLimits {
+        fn min(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        RelayLimits {
+            max_message_length: min(self.max_message_length, other.max_message_length),
+            max_event_tags: min(self.max_event_tags, other.max_event_tags),
+            max_content_length: min(self.max_content_length, other.max_content_length),
+        }
+    }
+
+    /// Limits the estimate exceeds; empty if the event fits.
+    pub fn check(&self, size: &EventSize) -> Vec<BudgetWarning> {
+        let mut warnings = Vec::new();
+        if let Some(max) = self.max_event_tags.filter(|max| size.tags > *max) {
+            warnings.push(BudgetWarning::TooManyTags {
+                tags: size.tags,
+                max,
+            });
+        }
+        if let Some(max) = self.max_content_length.filter(|max| size.content_chars > *max) {
+            warnings.push(BudgetWarning::ContentTooLong {
+                chars: size.content_chars,
+                max,
+            });
+        }
+        if let Some(max) = self.max_message_length.filter(|max| size.bytes > *max) {
+            warnings.push(BudgetWarning::EventTooLarge {
+                bytes: size.bytes,
+                max,
+            });
+        }
+        warnings
+    }
+
+    pub fn fits(&self, size: &EventSize) -> bool {
+        self.check(size).is_empty()
+    }
+}
+
+/// Conservative limits matching the NIP-11 example relay, which many
+/// public relays stay close to.
+impl Default for RelayLimits {
+    fn default() -> Self {
+        RelayLimits {
+            max_message_length: Some(16_384),
+            max_event_tags: Some(100),
+            max_content_length: Some(8_196),
+        }
+    }
+}
+
+/// Estimated size of an event on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventSize {
+    /// Serialized `["EVENT", <event>]` message size
+    pub bytes: usize,
+    pub tags: usize,
+    pub content_chars: usize,
+}
+
+impl EventSize {
+    pub fn estimate(tags: &[Tag], content: &str) -> Self {
+        let tag_bytes = serde_json::to_string(tags).map_or(0, |json| json.len());
+        let content_bytes = serde_json::to_string(content).map_or(0, |json| json.len());
+
+        EventSize {
+            bytes: EVENT_OVERHEAD_BYTES + tag_bytes + content_bytes,
+            tags: tags.len(),
+            content_chars: content.chars().count(),
+        }
+    }
+
+    /// Exact size of a signed event.
+    pub fn of(event: &Event) -> Self {
+        let json = serde_json::to_string(event).map_or(0, |json| json.len());
+
+        EventSize {
+            bytes: json + r#"["EVENT",]"#.len(),
+            tags: event.tags.len(),
+            content_chars: event.content.chars().count(),
+        }
+    }
+}
+
+/// A relay limit an event would exceed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetWarning {
+    TooManyTags { tags: usize, max: usize },
+    ContentTooLong { chars: usize, max: usize },
+    EventTooLarge { bytes: usize, max: usize },
+}
+
+impl std::fmt::Display for BudgetWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetWarning::TooManyTags { tags, max } => {
+                write!(f, "event has {tags} tags, relay limit is {max}")
+            }
+            BudgetWarning::ContentTooLong { chars, max } => {
+                write!(f, "content is {chars} characters, relay limit is {max}")
+            }
+            BudgetWarning::EventTooLarge { bytes, max } => {
+                write!(f, "event is about {bytes} bytes, relay limit is {max}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr::{EventBuilder, Keys, Kind};
+
+    use super::*;
+    use crate::KIND_SERVICE_CARD;
+
+    #[test]
+    fn estimate_covers_the_signed_event() {
+        let keys = Keys::generate();
+        for (tag_count, content) in [(0, ""), (3, "hello"), (40, "tabs\tand \"quotes\"")] {
+            let tags: Vec<Tag> = (0..tag_count)
+                .map(|i| Tag::parse(["c", &format!("tool.t{i}"), "A tool"]).unwrap())
+                .collect();
+            let estimate = EventSize::estimate(&tags, content);
+            let event = EventBuilder::new(Kind::Custom(KIND_SERVICE_CARD), content)
+                .tags(tags)
+                .sign_with_keys(&keys)
+                .unwrap();
+            let actual = EventSize::of(&event);
+
+            assert_eq!(estimate.tags, actual.tags);
+            assert_eq!(estimate.content_chars, actual.content_chars);
+            assert!(estimate.bytes >= actual.bytes, "{estimate:?} < {actual:?}");
+            assert!(estimate.bytes - actual.bytes < 64, "{estimate:?} vs {actual:?}");
+        }
+    }
+
+    #[test]
+    fn content_is_counted_in_chars() {
+        let size = EventSize::estimate(&[], "ééé");
+        assert_eq!(size.content_chars, 3);
+    }
+
+    #[test]
+    fn check_reports_every_exceeded_limit() {
+        let limits = RelayLimits {
+            max_message_length: Some(1_000),
+            max_event_tags: Some(10),
+            max_content_length: Some(100),
+        };
+        let small = EventSize {
+            bytes: 1_000,
+            tags: 10,
+            content_chars: 100,
+        };
+        assert!(limits.fits(&small));
+
+        let large = EventSize {
+            bytes: 1_001,
+            tags: 11,
+            content_chars: 101,
+        };
+        assert_eq!(
+            limits.check(&large),
+            [
+                BudgetWarning::TooManyTags { tags: 11, max: 10 },
+                BudgetWarning::ContentTooLong {
+                    chars: 101,
+                    max: 100
+                },
+                BudgetWarning::EventTooLarge {
+                    bytes: 1_001,
+                    max: 1_000
+                },
+            ]
+        );
+        assert!(RelayLimits::UNLIMITED.fits(&large));
+    }
+
+    #[test]
+    fn min_keeps_the_tightest_limits() {
+        let a = RelayLimits {
+            max_message_length: Some(1_000),
+            max_event_tags: None,
+            max_content_length: Some(100),
+        };
+        let b = RelayLimits {
+            max_message_length: Some(2_000),
+            max_event_tags: Some(50),
+            max_content_length: None,
+        };
+        assert_eq!(
+            a.min(b),
+            RelayLimits {
+                max_message_length: Some(1_000),
+                max_event_tags: Some(50),
+                max_content_length: Some(100),
+            }
+        );
+        assert_eq!(a.min(RelayLimits::UNLIMITED), a);
+    }
+}
@@ -0,0 +1,479 @@
+//! Local discovery cache
+//!
+//! Feature-gated behind `cache` feature. Keeps service cards, overflow
+//! events and heartbeats in a SQLite file, indexed by author, capability
+//! and protocol, so repeated queries don't have to wait for relays.
+//! Replaceable events keep only their newest version.
+
+use std::future::{self, Future};
+use std::path::Path;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use nostr::nips::nip01::Coordinate;
+use nostr::{Event, JsonUtil, PublicKey, RelayUrl, Timestamp};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::overflow::assemble_cards;
+use crate::service_card::primary_language;
+use crate::{
+    vocabulary, DiscoveryFilter, Error, Heartbeat, OverflowPart, ServiceCard, SuccessorChain,
+    KIND_HEARTBEAT, KIND_SERVICE_CARD, KIND_SERVICE_CARD_OVERFLOW, LABEL_LANGUAGE_NAMESPACE,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id TEXT PRIMARY KEY,
+        kind INTEGER NOT NULL,
+        pubkey TEXT NOT NULL,
+        d TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        json TEXT NOT NULL,
+        UNIQUE (kind, pubkey, d)
+    );
+    CREATE INDEX IF NOT EXISTS events_author ON events (pubkey, kind);
+    CREATE TABLE IF NOT EXISTS capabilities (
+        event_id TEXT NOT NULL,
+        capability TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS capabilities_id ON capabilities (capability);
+    CREATE TABLE IF NOT EXISTS protocols (
+        event_id TEXT NOT NULL,
+        protocol TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS protocols_id ON protocols (protocol);
+    CREATE TABLE IF NOT EXISTS languages (
+        event_id TEXT NOT NULL,
+        language TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS languages_id ON languages (language);
+    CREATE TABLE IF NOT EXISTS syncs (
+        relay TEXT PRIMARY KEY,
+        synced_at INTEGER NOT NULL
+    );
+";
+
+/// On-disk store of discovery events.
+pub struct DiscoveryCache {
+    conn: Connection,
+}
+
+impl DiscoveryCache {
+    /// Open or create a cache file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let conn = Connection::open(path).map_err(cache_error)?;
+        conn.execute_batch(SCHEMA).map_err(cache_error)?;
+        Ok(Self { conn })
+    }
+
+    /// Store a card, overflow or heartbeat event.
+    ///
+    /// Returns `false` if the event is of another kind or a newer version
+    /// is already stored.
+    pub fn insert(&mut self, event: &Event) -> Result<bool, Error> {
+        let kind = event.kind.as_u16();
+        if ![KIND_SERVICE_CARD, KIND_SERVICE_CARD_OVERFLOW, KIND_HEARTBEAT].contains(&kind) {
+            return Ok(false);
+        }
+        let pubkey = event.pubkey.to_hex();
+        let d = event.tags.identifier().unwrap_or_default();
+
+        let tx = self.conn.transaction().map_err(cache_error)?;
+        let stored: Option<(String, u64)> = tx
+            .query_row(
+                "SELECT id, created_at FROM events WHERE kind = ?1 AND pubkey = ?2 AND d = ?3",
+                params![kind, pubkey, d],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(cache_error)?;
+        if let Some((id, created_at)) = stored {
+            if created_at >= event.created_at.as_u64() {
+                return Ok(false);
+            }
+            for sql in [
+                "DELETE FROM capabilities WHERE event_id = ?1",
+                "DELETE FROM protocols WHERE event_id = ?1",
+                "DELETE FROM languages WHERE event_id = ?1",
+                "DELETE FROM events WHERE id = ?1",
+            ] {
+                tx.execute(sql, params![id]).map_err(cache_error)?;
+            }
+        }
+
+        let id = event.id.to_hex();
+        tx.execute(
+            "INSERT INTO events (id, kind, pubkey, d, created_at, json)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, kind, pubkey, d, event.created_at.as_u64(), event.as_json()],
+        )
+        .map_err(cache_error)?;
+
+        // Index what relays index: `c`, `r` and language `l` tag values
+        for tag in event.tags.iter() {
+            let values = tag.as_slice();
+            let table = match values.first().map(|s| s.as_str()) {
+                Some("c") if values.len() >= 2 => "capabilities (event_id, capability)",
+                Some("r") if values.len() >= 2 => "protocols (event_id, protocol)",
+                Some("l") if values.len() >= 3 && values[2] == LABEL_LANGUAGE_NAMESPACE => {
+                    "languages (event_id, language)"
+                }
+                _ => continue,
+            };
+            tx.execute(
+                &format!("INSERT INTO {table} VALUES (?1, ?2)"),
+                params![id, values[1]],
+            )
+            .map_err(cache_error)?;
+        }
+
+        tx.commit().map_err(cache_error)?;
+        Ok(true)
+    }
+
+    /// When the cache was last synced from a relay.
+    pub fn last_sync(&self, relay: &RelayUrl) -> Result<Option<Timestamp>, Error> {
+        self.conn
+            .query_row(
+                "SELECT synced_at FROM syncs WHERE relay = ?1",
+                params![relay.as_str()],
+                |row| row.get::<_, u64>(0),
+            )
+            .optional()
+            .map(|synced_at| synced_at.map(Timestamp::from))
+            .map_err(cache_error)
+    }
+
+    /// Record a sync from a relay.
+    pub fn set_last_sync(&self, relay: &RelayUrl, synced_at: Timestamp) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO syncs (relay, synced_at) VALUES (?1, ?2)",
+                params![relay.as_str(), synced_at.as_u64()],
+            )
+            .map_err(cache_error)?;
+        Ok(())
+    }
+
+    /// Discover agents in the cache, like
+    /// [`AgentDiscoveryClient::discover_agents`](crate::AgentDiscoveryClient::discover_agents)
+    /// does on relays.
+    pub fn discover_agents(
+        &self,
+        discovery: &DiscoveryFilter,
+    ) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let requirements = discovery.requirements()?;
+
+        let mut sql = format!(
+            "SELECT DISTINCT e.json, e.created_at FROM events e{} WHERE e.kind IN ({}, {})",
+            if requirements.is_empty() { "" } else { " JOIN capabilities c ON c.event_id = e.id" },
+            KIND_SERVICE_CARD,
+            KIND_SERVICE_CARD_OVERFLOW,
+        );
+        let mut values = Vec::new();
+        if let Some(pk) = discovery.author {
+            sql.push_str(" AND e.pubkey = ?");
+            values.push(pk.to_hex());
+        }
+        if !requirements.is_empty() {
+            let expanded: Vec<String> = requirements
+                .iter()
+                .flat_map(|req| vocabulary::expand(&req.id))
+                .collect();
+            sql.push_str(&format!(" AND c.capability IN ({})", placeholders(expanded.len())));
+            values.extend(expanded);
+        }
+        let languages = &discovery.languages;
+        if !languages.is_empty() {
+            // Overflow events carry no language labels
+            sql.push_str(&format!(
+                " AND (e.kind = {KIND_SERVICE_CARD_OVERFLOW} OR e.id IN
+                   (SELECT event_id FROM languages WHERE language IN ({})))",
+                placeholders(languages.len())
+            ));
+            values.extend(languages.iter().map(|lang| primary_language(lang)));
+        }
+        sql.push_str(&format!(" ORDER BY e.created_at DESC LIMIT {}", discovery.limit));
+
+        run_ready(assemble_cards(
+            self.events(&sql, values)?,
+            discovery,
+            |author, card_id| future::ready(self.overflow_parts(author, &card_id)),
+            |root| future::ready(self.get_service_card(&root)),
+        ))
+    }
+
+    /// Cards advertising a protocol id such as `a2a` or `dvm`, newest first.
+    pub fn agents_with_protocol(
+        &self,
+        protocol: &str,
+        limit: usize,
+    ) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let sql = format!(
+            "SELECT DISTINCT e.json, e.created_at FROM events e
+             JOIN protocols p ON p.event_id = e.id
+             WHERE e.kind = {KIND_SERVICE_CARD} AND p.protocol = ?
+             ORDER BY e.created_at DESC LIMIT {limit}"
+        );
+        let mut cards = self.cards(&sql, vec![protocol.to_string()])?;
+        for (card, event) in &mut cards {
+            self.merge_overflow(card, event.pubkey)?;
+        }
+        Ok(cards)
+    }
+
+    /// The cached service card at a coordinate, if any and not expired.
+    pub fn get_service_card(
+        &self,
+        coordinate: &Coordinate,
+    ) -> Result<Option<(ServiceCard, Event)>, Error> {
+        let sql = format!(
+            "SELECT json FROM events WHERE kind = {KIND_SERVICE_CARD} AND pubkey = ? AND d = ?"
+        );
+        let values = vec![coordinate.public_key.to_hex(), coordinate.identifier.clone()];
+        let Some((mut card, event)) = self.cards(&sql, values)?.pop() else {
+            return Ok(None);
+        };
+        self.merge_overflow(&mut card, event.pubkey)?;
+        Ok(Some((card, event)))
+    }
+
+    /// Every cached service card of an author, by card id.
+    pub fn get_service_cards(&self, author: PublicKey) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let mut cards = self.get_service_cards_by_authors(&[author])?;
+        for (card, event) in &mut cards {
+            self.merge_overflow(card, event.pubkey)?;
+        }
+        Ok(cards)
+    }
+
+    /// Every cached service card of several authors, by author and card id.
+    ///
+    /// Overflow capabilities of split cards are not merged.
+    pub fn get_service_cards_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let sql = format!(
+            "SELECT json FROM events WHERE kind = {KIND_SERVICE_CARD} AND pubkey IN ({})
+             ORDER BY pubkey, d",
+            placeholders(authors.len())
+        );
+        self.cards(&sql, authors.iter().map(|pk| pk.to_hex()).collect())
+    }
+
+    /// Cached heartbeats of several agents, skipping expired ones.
+    pub fn get_heartbeats_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(Heartbeat, Event)>, Error> {
+        let sql = format!(
+            "SELECT json FROM events WHERE kind = {KIND_HEARTBEAT} AND pubkey IN ({})",
+            placeholders(authors.len())
+        );
+
+        let mut heartbeats = Vec::new();
+        for event in self.events(&sql, authors.iter().map(|pk| pk.to_hex()).collect())? {
+            match Heartbeat::try_from(&event) {
+                Ok(hb) if hb.is_expired() => {}
+                Ok(hb) => heartbeats.push((hb, event)),
+                Err(e) => eprintln!("Failed to parse cached heartbeat: {e}"),
+            }
+        }
+        Ok(heartbeats)
+    }
+
+    /// Follow successor pointers through the cache, like
+    /// [`AgentDiscoveryClient::follow_successors`](crate::AgentDiscoveryClient::follow_successors).
+    pub fn follow_successors(
+        &self,
+        card: ServiceCard,
+        event: Event,
+    ) -> Result<SuccessorChain, Error> {
+        run_ready(SuccessorChain::follow(card, event, |successor| {
+            future::ready(self.get_service_card(&successor))
+        }))
+    }
+
+    /// Merge a split card's cached overflow events into it.
+    fn merge_overflow(&self, card: &mut ServiceCard, author: PublicKey) -> Result<(), Error> {
+        if card.overflow.is_some() {
+            let parts = self.overflow_parts(author, &card.id)?;
+            card.merge_overflow(parts);
+        }
+        Ok(())
+    }
+
+    /// Cached overflow events of a card.
+    fn overflow_parts(&self, author: PublicKey, card_id: &str) -> Result<Vec<OverflowPart>, Error> {
+        let sql = format!(
+            "SELECT json FROM events WHERE kind = {KIND_SERVICE_CARD_OVERFLOW} AND pubkey = ?"
+        );
+        Ok(self
+            .events(&sql, vec![author.to_hex()])?
+            .iter()
+            .filter_map(|event| OverflowPart::try_from(event).ok())
+            .filter(|part| part.card_id == card_id)
+            .collect())
+    }
+
+    /// Parse the service cards a query returns, skipping expired ones.
+    fn cards(&self, sql: &str, values: Vec<String>) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let mut cards = Vec::new();
+        for event in self.events(sql, values)? {
+            match ServiceCard::try_from(&event) {
+                Ok(card) if card.is_expired() => {}
+                Ok(card) => cards.push((card, event)),
+                Err(e) => eprintln!("Failed to parse cached service card: {e}"),
+            }
+        }
+        Ok(cards)
+    }
+
+    /// Events a query returns; the first column must hold the event JSON.
+    fn events(&self, sql: &str, values: Vec<String>) -> Result<Vec<Event>, Error> {
+        let mut stmt = self.conn.prepare(sql).map_err(cache_error)?;
+        let rows = stmt
+            .query_map(params_from_iter(values), |row| row.get::<_, String>(0))
+            .map_err(cache_error)?;
+
+        let mut events = Vec::new();
+        for json in rows {
+            let json = json.map_err(cache_error)?;
+            events.push(Event::from_json(json).map_err(|e| Error::CacheError(e.to_string()))?);
+        }
+        Ok(events)
+    }
+}
+
+/// Output of a future built from cache lookups, which never has to wait.
+fn run_ready<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("cache lookups are ready at once"),
+    }
+}
+
+/// `?, ?, ...` for an `IN` list of `n` values.
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+fn cache_error(e: rusqlite::Error) -> Error {
+    Error::CacheError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr::{Keys, Kind};
+
+    use super::*;
+    use crate::testing;
+
+    fn index_rows(cache: &DiscoveryCache, table: &str) -> usize {
+        cache
+            .conn
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn newer_versions_replace_older_ones() {
+        let mut cache = DiscoveryCache::open(":memory:").unwrap();
+        let keys = Keys::generate();
+        let v1 = ServiceCard::builder("agent", "Agent v1").build().to_tags();
+        let v2 = ServiceCard::builder("agent", "Agent v2").build().to_tags();
+        let v1 = testing::event_at(&keys, 1_000, KIND_SERVICE_CARD, v1);
+        let v2 = testing::event_at(&keys, 2_000, KIND_SERVICE_CARD, v2);
+
+        assert!(cache.insert(&v1).unwrap());
+        assert!(cache.insert(&v2).unwrap());
+        assert!(!cache.insert(&v1).unwrap());
+        assert!(!cache.insert(&v2).unwrap());
+
+        let coordinate =
+            Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), keys.public_key()).identifier("agent");
+        let (card, event) = cache.get_service_card(&coordinate).unwrap().unwrap();
+        assert_eq!(card.name, "Agent v2");
+        assert_eq!(event.id, v2.id);
+        assert_eq!(cache.get_service_cards(keys.public_key()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn replacing_a_card_drops_its_old_index_rows() {
+        let mut cache = DiscoveryCache::open(":memory:").unwrap();
+        let keys = Keys::generate();
+        let v1 = ServiceCard::builder("agent", "Agent")
+            .capability("text.summarization", "Summaries")
+            .capability("text.translation", "Translation")
+            .language("en")
+            .language("de")
+            .build();
+        let v2 = ServiceCard::builder("agent", "Agent")
+            .capability("text.translation", "Translation")
+            .language("fr")
+            .build();
+        cache.insert(&testing::event_at(&keys, 1_000, KIND_SERVICE_CARD, v1.to_tags())).unwrap();
+        let v2 = testing::event_at(&keys, 2_000, KIND_SERVICE_CARD, v2.to_tags());
+        cache.insert(&v2).unwrap();
+
+        // Only the new version's `c` tags, bare ancestor ones included
+        let c_tags = v2.tags.iter().filter(|tag| tag.as_slice()[0] == "c").count();
+        assert_eq!(index_rows(&cache, "capabilities"), c_tags);
+        assert_eq!(index_rows(&cache, "languages"), 1);
+        let discover = |filter: DiscoveryFilter| cache.discover_agents(&filter).unwrap();
+        assert!(discover(DiscoveryFilter::new(10).capability("text.summarization")).is_empty());
+        assert!(discover(DiscoveryFilter::new(10).language("en")).is_empty());
+        assert_eq!(discover(DiscoveryFilter::new(10).language("fr")).len(), 1);
+    }
+
+    #[test]
+    fn discover_agents_matches_through_the_capability_index() {
+        let mut cache = DiscoveryCache::open(":memory:").unwrap();
+        let summarizer = ServiceCard::builder("summarizer", "Summarizer")
+            .capability("text.summarization", "Summaries")
+            .language("en")
+            .build();
+        let translator = ServiceCard::builder("translator", "Translator")
+            .capability("text.translation", "Translation")
+            .language("de")
+            .build();
+        for (card, created_at) in [(summarizer, 1_000), (translator, 2_000)] {
+            let event =
+                testing::event_at(&Keys::generate(), created_at, KIND_SERVICE_CARD, card.to_tags());
+            cache.insert(&event).unwrap();
+        }
+
+        let ids = |capabilities: &[&str], languages: &[&str]| -> Vec<String> {
+            let mut filter = DiscoveryFilter::new(10);
+            filter.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+            filter.languages = languages.iter().map(|lang| lang.to_string()).collect();
+            cache
+                .discover_agents(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|(card, _)| card.id)
+                .collect()
+        };
+        assert_eq!(ids(&[], &[]), ["translator", "summarizer"]);
+        assert_eq!(ids(&["text.summarization"], &[]), ["summarizer"]);
+        // Aliases expand to the canonical id
+        assert_eq!(ids(&["summarize"], &[]), ["summarizer"]);
+        assert_eq!(ids(&["text.translation"], &["de"]), ["translator"]);
+        assert!(ids(&["text.translation"], &["en"]).is_empty());
+        assert!(ids(&["image.generation"], &[]).is_empty());
+    }
+
+    #[test]
+    fn syncs_are_recorded_per_relay() {
+        let cache = DiscoveryCache::open(":memory:").unwrap();
+        let damus = RelayUrl::parse("wss://relay.damus.io").unwrap();
+        let nos = RelayUrl::parse("wss://nos.lol").unwrap();
+
+        cache.set_last_sync(&damus, Timestamp::from(1_000)).unwrap();
+        cache.set_last_sync(&damus, Timestamp::from(2_000)).unwrap();
+        assert_eq!(cache.last_sync(&damus).unwrap(), Some(Timestamp::from(2_000)));
+        assert_eq!(cache.last_sync(&nos).unwrap(), None);
+    }
+}
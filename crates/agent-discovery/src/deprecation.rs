@@ -0,0 +1,342 @@
+//! Card deprecation
+//!
+//! A card can retire itself and point at its replacement by coordinate
+//! (`31990:<pubkey>:<d>`), possibly under a different pubkey.
+
+use std::future::Future;
+
+use nostr::nips::nip01::Coordinate;
+use nostr::{Event, Tag, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, ServiceCard, KIND_SERVICE_CARD};
+
+/// Marker on the `a` tag that points at a successor card
+pub const SUCCESSOR_MARKER: &str = "successor";
+
+/// Successor hops followed before giving up on a chain
+pub const MAX_SUCCESSOR_HOPS: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// When the agent stops serving requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<Timestamp>,
+
+    /// Coordinate of the replacement card
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "coordinate")]
+    pub successor: Option<Coordinate>,
+}
+
+impl Deprecation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn sunset(mut self, sunset: Timestamp) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    pub fn successor(mut self, successor: Coordinate) -> Self {
+        self.successor = Some(successor);
+        self
+    }
+
+    /// Whether the sunset date has passed.
+    pub fn is_sunset(&self) -> bool {
+        self.sunset.is_some_and(|sunset| sunset <= Timestamp::now())
+    }
+
+    /// Check that the successor points at a service card.
+    pub fn validate(&self) -> Result<(), Error> {
+        match &self.successor {
+            Some(successor) if successor.kind.as_u16() != KIND_SERVICE_CARD => Err(
+                Error::ParseError(format!("successor is not a service card: {successor}")),
+            ),
+            Some(successor) if successor.identifier.is_empty() => Err(Error::ParseError(format!(
+                "successor has no card id: {successor}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_tags(&self) -> Vec<Tag> {
+        let mut tags = vec![match &self.reason {
+            Some(reason) => Tag::parse(["deprecated", reason]).unwrap(),
+            None => Tag::parse(["deprecated"]).unwrap(),
+        }];
+        if let Some(sunset) = self.sunset {
+            tags.push(Tag::parse(["sunset", &sunset.to_string()]).unwrap());
+        }
+        if let Some(successor) = &self.successor {
+            tags.push(Tag::parse(["a", &successor.to_string(), "", SUCCESSOR_MARKER]).unwrap());
+        }
+        tags
+    }
+
+    /// Parse a successor coordinate as carried in the `a` tag.
+    pub fn parse_successor(s: &str) -> Result<Coordinate, Error> {
+        s.parse()
+            .map_err(|e| Error::ParseError(format!("invalid successor coordinate '{s}': {e}")))
+    }
+}
+
+/// A deprecated card followed by its successors, oldest first.
+#[derive(Debug, Clone)]
+pub struct SuccessorChain {
+    pub cards: Vec<(ServiceCard, Event)>,
+    pub end: ChainEnd,
+}
+
+impl SuccessorChain {
+    /// Follow successor pointers from a card, fetching each successor with
+    /// `lookup`.
+    ///
+    /// Stops at a card without successor, a successor that can't be found,
+    /// a loop, or after [`MAX_SUCCESSOR_HOPS`] hops.
+    pub async fn follow<F, Fut>(
+        card: ServiceCard,
+        event: Event,
+        mut lookup: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut(Coordinate) -> Fut,
+        Fut: Future<Output = Result<Option<(ServiceCard, Event)>, Error>>,
+    {
+        let mut seen = vec![(event.pubkey, card.id.clone())];
+        let mut cards = vec![(card, event)];
+
+        let end = loop {
+            let (card, _) = cards.last().expect("chain starts with a card");
+            let Some(successor) = card.successor().cloned() else {
+                break ChainEnd::Resolved;
+            };
+
+            let key = (successor.public_key, successor.identifier.clone());
+            if seen.contains(&key) {
+                break ChainEnd::Loop(successor);
+            }
+            if cards.len() > MAX_SUCCESSOR_HOPS {
+                break ChainEnd::TooLong;
+            }
+
+            match lookup(successor.clone()).await? {
+                Some(next) => {
+                    seen.push(key);
+                    cards.push(next);
+                }
+                None => break ChainEnd::Missing(successor),
+            }
+        };
+
+        Ok(SuccessorChain { cards, end })
+    }
+
+    /// The newest card reached.
+    pub fn current(&self) -> &(ServiceCard, Event) {
+        self.cards.last().expect("chain starts with a card")
+    }
+
+    /// Whether any successor was found.
+    pub fn moved(&self) -> bool {
+        self.cards.len() > 1
+    }
+}
+
+/// Why following a successor chain stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEnd {
+    /// The last card has no successor
+    Resolved,
+
+    /// The successor was not found on the connected relays
+    Missing(Coordinate),
+
+    /// The successor points back at a card already in the chain
+    Loop(Coordinate),
+
+    /// More than [`MAX_SUCCESSOR_HOPS`] successors
+    TooLong,
+}
+
+impl std::fmt::Display for ChainEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainEnd::Resolved => write!(f, "resolved"),
+            ChainEnd::Missing(coordinate) => write!(f, "successor {coordinate} not found"),
+            ChainEnd::Loop(coordinate) => write!(f, "successor {coordinate} loops back"),
+            ChainEnd::TooLong => write!(f, "more than {MAX_SUCCESSOR_HOPS} successors"),
+        }
+    }
+}
+
+/// Serialize coordinates as `kind:pubkey:d` strings.
+mod coordinate {
+    use nostr::nips::nip01::Coordinate;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        coordinate: &Option<Coordinate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        coordinate
+            .as_ref()
+            .map(|c| c.to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Coordinate>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| s.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nostr::{Keys, Kind};
+
+    use super::*;
+    use crate::testing;
+
+    fn coordinate(keys: &Keys, id: &str) -> Coordinate {
+        Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), keys.public_key()).identifier(id)
+    }
+
+    /// Cards `card-0`..`card-<n>` signed by `keys`, each pointing at `successors[i]`.
+    fn cards(keys: &Keys, successors: &[Option<&str>]) -> Vec<(ServiceCard, Event)> {
+        successors
+            .iter()
+            .enumerate()
+            .map(|(i, successor)| {
+                let mut deprecation = Deprecation::new();
+                deprecation.successor = successor.map(|id| coordinate(keys, id));
+                let card = ServiceCard::builder(format!("card-{i}"), "Agent")
+                    .deprecation(deprecation)
+                    .build();
+                let event = card.to_event(keys).unwrap();
+                (card, event)
+            })
+            .collect()
+    }
+
+    async fn follow(cards: Vec<(ServiceCard, Event)>) -> SuccessorChain {
+        let mut cards = cards.into_iter();
+        let (card, event) = cards.next().unwrap();
+        let by_id: HashMap<_, _> = cards.map(|next| (next.0.id.clone(), next)).collect();
+        SuccessorChain::follow(card, event, |successor| {
+            let next = by_id.get(&successor.identifier).cloned();
+            async move { Ok(next) }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn successor_must_be_a_service_card_with_an_id() {
+        let keys = Keys::generate();
+        assert!(Deprecation::new().validate().is_ok());
+        assert!(Deprecation::new()
+            .successor(coordinate(&keys, "v2"))
+            .validate()
+            .is_ok());
+
+        let other_kind = Coordinate::new(Kind::Metadata, keys.public_key()).identifier("v2");
+        assert!(Deprecation::new().successor(other_kind).validate().is_err());
+        assert!(Deprecation::new()
+            .successor(coordinate(&keys, ""))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn successor_parses_from_its_tag_value() {
+        let successor = coordinate(&Keys::generate(), "v2");
+        assert_eq!(
+            Deprecation::parse_successor(&successor.to_string()).unwrap(),
+            successor
+        );
+        assert!(Deprecation::parse_successor("31990:not-a-pubkey:v2").is_err());
+    }
+
+    #[test]
+    fn tags_carry_every_field() {
+        let successor = coordinate(&Keys::generate(), "v2");
+        let deprecation = Deprecation::new()
+            .reason("use v2")
+            .sunset(Timestamp::from(1_704_067_200))
+            .successor(successor.clone());
+        assert_eq!(
+            deprecation.to_tags(),
+            testing::tags(&[
+                &["deprecated", "use v2"],
+                &["sunset", "1704067200"],
+                &["a", &successor.to_string(), "", SUCCESSOR_MARKER],
+            ])
+        );
+        assert_eq!(
+            Deprecation::new().to_tags(),
+            testing::tags(&[&["deprecated"]])
+        );
+    }
+
+    #[tokio::test]
+    async fn chain_resolves_at_a_card_without_successor() {
+        let keys = Keys::generate();
+        let chain = follow(cards(&keys, &[Some("card-1"), Some("card-2"), None])).await;
+        assert_eq!(chain.end, ChainEnd::Resolved);
+        assert!(chain.moved());
+        assert_eq!(chain.current().0.id, "card-2");
+    }
+
+    #[tokio::test]
+    async fn chain_stops_at_a_missing_successor() {
+        let keys = Keys::generate();
+        let chain = follow(cards(&keys, &[Some("card-1"), Some("gone")])).await;
+        assert_eq!(chain.end, ChainEnd::Missing(coordinate(&keys, "gone")));
+        assert_eq!(chain.current().0.id, "card-1");
+    }
+
+    #[tokio::test]
+    async fn chain_detects_loops() {
+        let keys = Keys::generate();
+        let chain = follow(cards(
+            &keys,
+            &[Some("card-1"), Some("card-2"), Some("card-0")],
+        ))
+        .await;
+        assert_eq!(chain.end, ChainEnd::Loop(coordinate(&keys, "card-0")));
+        assert_eq!(chain.cards.len(), 3);
+
+        let chain = follow(cards(&keys, &[Some("card-0")])).await;
+        assert_eq!(chain.end, ChainEnd::Loop(coordinate(&keys, "card-0")));
+        assert!(!chain.moved());
+    }
+
+    #[tokio::test]
+    async fn chain_gives_up_after_max_hops() {
+        let keys = Keys::generate();
+        let ids: Vec<String> = (1..=MAX_SUCCESSOR_HOPS + 1)
+            .map(|i| format!("card-{i}"))
+            .collect();
+        let mut successors: Vec<Option<&str>> = ids.iter().map(|id| Some(id.as_str())).collect();
+        successors.push(None);
+
+        let chain = follow(cards(&keys, &successors)).await;
+        assert_eq!(chain.end, ChainEnd::TooLong);
+        assert_eq!(chain.cards.len(), MAX_SUCCESSOR_HOPS + 1);
+    }
+}
@@ -13,12 +13,21 @@ pub enum Error {
     #[error("Relay error: {0}")]
     RelayError(String),
 
+    #[error("Authentication error: {0}")]
+    AuthError(String),
+
     #[error("Signing error: {0}")]
     SigningError(String),
 
     #[error("Parse error: {0}")]
     ParseError(String),
 
+    #[error("Event too large: {0}")]
+    EventTooLarge(String),
+
+    #[error("Cache error: {0}")]
+    CacheError(String),
+
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
@@ -0,0 +1,64 @@
+//! Discovery filters
+//!
+//! What a client is looking for when it discovers agents, shared by the relay
+//! client and the local cache.
+
+use nostr::PublicKey;
+
+use crate::{CapabilityRequirement, Error};
+
+/// Which service cards to discover.
+///
+/// Capabilities are requirement strings such as `translation` or
+/// `translation@^2`; a card matching any of them qualifies. Languages are
+/// ISO 639-1 codes; cards must speak all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryFilter {
+    pub limit: usize,
+    pub author: Option<PublicKey>,
+    pub capabilities: Vec<String>,
+    pub languages: Vec<String>,
+
+    /// Minimum NIP-13 proof of work of the card's event id; `0` accepts all
+    pub min_pow: u8,
+}
+
+impl DiscoveryFilter {
+    /// Up to `limit` cards of any author, capability and language.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            author: None,
+            capabilities: Vec::new(),
+            languages: Vec::new(),
+            min_pow: 0,
+        }
+    }
+
+    pub fn author(mut self, author: PublicKey) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Add a capability requirement, e.g. `translation@^2`.
+    pub fn capability(mut self, requirement: impl Into<String>) -> Self {
+        self.capabilities.push(requirement.into());
+        self
+    }
+
+    /// Add a language as an ISO 639-1 code, e.g. `de`.
+    pub fn language(mut self, lang: impl Into<String>) -> Self {
+        self.languages.push(lang.into());
+        self
+    }
+
+    pub fn min_pow(mut self, bits: u8) -> Self {
+        self.min_pow = bits;
+        self
+    }
+
+    /// Parse the capability requirements.
+    pub fn requirements(&self) -> Result<Vec<CapabilityRequirement>, Error> {
+        self.capabilities.iter().map(|c| c.parse()).collect()
+    }
+}
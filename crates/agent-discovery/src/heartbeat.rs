@@ -1,18 +1,50 @@
 //! Agent Heartbeat
 
+use std::collections::BTreeMap;
+
 use nostr::event::{Event, EventBuilder, Kind};
 use nostr::key::Keys;
-use nostr::Tag;
+use nostr::{Tag, Timestamp};
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, KIND_HEARTBEAT, LABEL_HEARTBEAT, LABEL_NAMESPACE};
+use crate::{
+    vocabulary, Error, ServiceCard, HEARTBEAT_EXPIRATION_INTERVALS, HEARTBEAT_INTERVAL_SECS,
+    KIND_HEARTBEAT, LABEL_HEARTBEAT, LABEL_NAMESPACE,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// Agent availability carried in the `s` tag.
+///
+/// Unknown values parse into [`Status::Other`] so a newer publisher never
+/// makes its heartbeat unreadable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum Status {
     Available,
     Busy,
     Maintenance,
+
+    /// Finishing current jobs, not accepting new ones
+    Draining,
+
+    /// Shutting down or explicitly unreachable
+    Offline,
+
+    /// A status this version doesn't know
+    Other(String),
+}
+
+impl Status {
+    /// Whether the agent is accepting new requests.
+    pub fn is_available(&self) -> bool {
+        matches!(self, Status::Available)
+    }
+
+    /// Whether the agent is up, even if not accepting new requests.
+    ///
+    /// Unknown statuses count as online but never as available.
+    pub fn is_online(&self) -> bool {
+        !matches!(self, Status::Offline | Status::Maintenance)
+    }
 }
 
 impl std::fmt::Display for Status {
@@ -21,6 +53,9 @@ impl std::fmt::Display for Status {
             Status::Available => write!(f, "available"),
             Status::Busy => write!(f, "busy"),
             Status::Maintenance => write!(f, "maintenance"),
+            Status::Draining => write!(f, "draining"),
+            Status::Offline => write!(f, "offline"),
+            Status::Other(other) => write!(f, "{other}"),
         }
     }
 }
@@ -29,26 +64,90 @@ impl std::str::FromStr for Status {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "available" => Ok(Status::Available),
-            "busy" => Ok(Status::Busy),
-            "maintenance" => Ok(Status::Maintenance),
-            other => Err(Error::ParseError(format!("unknown status: {other}"))),
+        if s.is_empty() {
+            return Err(Error::ParseError("empty status".to_string()));
+        }
+        Ok(Status::from(s.to_string()))
+    }
+}
+
+impl From<String> for Status {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "available" => Status::Available,
+            "busy" => Status::Busy,
+            "maintenance" => Status::Maintenance,
+            "draining" => Status::Draining,
+            "offline" => Status::Offline,
+            _ => Status::Other(s),
         }
     }
 }
 
+impl From<Status> for String {
+    fn from(status: Status) -> Self {
+        status.to_string()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Heartbeat {
     pub service_card_id: String,
     pub status: Status,
+
+    /// NIP-40 expiration, so relays can drop presence of agents that stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<Timestamp>,
+
+    /// Current load, 0–100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<u8>,
+
+    /// Jobs waiting to be processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<u32>,
+
+    /// Maximum concurrent jobs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<u32>,
+
+    /// Agent software version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Seconds since the agent started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime: Option<u64>,
+
+    /// Human-readable status message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    /// Estimated time the agent is back, e.g. at the end of maintenance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_at: Option<Timestamp>,
+
+    /// Status overrides keyed by capability id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub capabilities: BTreeMap<String, Status>,
 }
 
 impl Heartbeat {
+    /// New heartbeat expiring after [`HEARTBEAT_EXPIRATION_INTERVALS`] missed intervals.
     pub fn new(service_card_id: impl Into<String>, status: Status) -> Self {
+        let ttl = HEARTBEAT_INTERVAL_SECS * HEARTBEAT_EXPIRATION_INTERVALS;
         Self {
             service_card_id: service_card_id.into(),
             status,
+            expiration: Some(Timestamp::from(Timestamp::now().as_u64() + ttl)),
+            load: None,
+            queue_depth: None,
+            capacity: None,
+            version: None,
+            uptime: None,
+            message: None,
+            return_at: None,
+            capabilities: BTreeMap::new(),
         }
     }
 
@@ -64,13 +163,137 @@ impl Heartbeat {
         Self::new(service_card_id, Status::Maintenance)
     }
 
+    pub fn draining(service_card_id: impl Into<String>) -> Self {
+        Self::new(service_card_id, Status::Draining)
+    }
+
+    pub fn offline(service_card_id: impl Into<String>) -> Self {
+        Self::new(service_card_id, Status::Offline)
+    }
+
+    /// Override the expiration; `None` publishes a heartbeat that never expires.
+    pub fn expiration(mut self, expiration: Option<Timestamp>) -> Self {
+        self.expiration = expiration;
+        self
+    }
+
+    /// Current load as a percentage; values above 100 are clamped.
+    pub fn load(mut self, load: u8) -> Self {
+        self.load = Some(load.min(100));
+        self
+    }
+
+    pub fn queue_depth(mut self, queue_depth: u32) -> Self {
+        self.queue_depth = Some(queue_depth);
+        self
+    }
+
+    pub fn capacity(mut self, capacity: u32) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn uptime(mut self, secs: u64) -> Self {
+        self.uptime = Some(secs);
+        self
+    }
+
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn return_at(mut self, return_at: Timestamp) -> Self {
+        self.return_at = Some(return_at);
+        self
+    }
+
+    /// Override the status for one capability (and the capabilities below it).
+    pub fn capability_status(mut self, capability_id: impl Into<String>, status: Status) -> Self {
+        self.capabilities.insert(capability_id.into(), status);
+        self
+    }
+
+    /// Effective status for a capability.
+    ///
+    /// Uses the override for the id or its nearest overridden ancestor
+    /// (`audio` covers `audio.transcription`), else the global status.
+    pub fn status_for(&self, capability_id: &str) -> &Status {
+        let mut id = capability_id;
+        loop {
+            if let Some(status) = self.capabilities.get(id) {
+                return status;
+            }
+            match id.rsplit_once('.') {
+                Some((parent, _)) => id = parent,
+                None => return &self.status,
+            }
+        }
+    }
+
+    /// Whether `card` offers `capability` (or an alias or descendant of it)
+    /// and this heartbeat reports that capability available.
+    pub fn is_available_for(&self, card: &ServiceCard, capability: &str) -> bool {
+        if !self.status.is_online() {
+            return false;
+        }
+
+        let ids = vocabulary::expand(capability);
+        card.capabilities
+            .iter()
+            .filter(|cap| ids.iter().any(|id| cap.matches(id)))
+            .any(|cap| self.status_for(&cap.id).is_available())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expiration.is_some_and(|exp| exp <= Timestamp::now())
+    }
+
     pub fn to_tags(&self) -> Vec<Tag> {
-        vec![
+        let mut tags = vec![
             Tag::parse(["L", LABEL_NAMESPACE]).unwrap(),
             Tag::parse(["l", LABEL_HEARTBEAT, LABEL_NAMESPACE]).unwrap(),
             Tag::parse(["d", &self.service_card_id]).unwrap(),
-            Tag::parse(["s", &self.status.to_string()]).unwrap(),
-        ]
+        ];
+
+        // Per-capability overrides go before the global status: parsers that
+        // predate them keep the last `s` tag they see.
+        for (capability_id, status) in &self.capabilities {
+            tags.push(Tag::parse(["s", &status.to_string(), capability_id]).unwrap());
+        }
+        tags.push(Tag::parse(["s", &self.status.to_string()]).unwrap());
+
+        if let Some(expiration) = self.expiration {
+            tags.push(Tag::parse(["expiration", &expiration.to_string()]).unwrap());
+        }
+        if let Some(load) = self.load {
+            tags.push(Tag::parse(["load", &load.to_string()]).unwrap());
+        }
+        if let Some(queue_depth) = self.queue_depth {
+            tags.push(Tag::parse(["queue", &queue_depth.to_string()]).unwrap());
+        }
+        if let Some(capacity) = self.capacity {
+            tags.push(Tag::parse(["capacity", &capacity.to_string()]).unwrap());
+        }
+        if let Some(version) = &self.version {
+            tags.push(Tag::parse(["version", version]).unwrap());
+        }
+        if let Some(uptime) = self.uptime {
+            tags.push(Tag::parse(["uptime", &uptime.to_string()]).unwrap());
+        }
+        if let Some(message) = &self.message {
+            tags.push(Tag::parse(["message", message]).unwrap());
+        }
+        if let Some(return_at) = self.return_at {
+            tags.push(Tag::parse(["return", &return_at.to_string()]).unwrap());
+        }
+
+        tags
     }
 
     /// Convenience method to sign directly. Equivalent to:
@@ -95,6 +318,15 @@ impl TryFrom<&Event> for Heartbeat {
     fn try_from(event: &Event) -> Result<Self, Self::Error> {
         let mut service_card_id = None;
         let mut status = None;
+        let mut expiration = None;
+        let mut load = None;
+        let mut queue_depth = None;
+        let mut capacity = None;
+        let mut version = None;
+        let mut uptime = None;
+        let mut message = None;
+        let mut return_at = None;
+        let mut capabilities = BTreeMap::new();
 
         for tag in event.tags.iter() {
             let values: Vec<&str> = tag.as_slice().iter().map(|s| s.as_str()).collect();
@@ -104,7 +336,24 @@ impl TryFrom<&Event> for Heartbeat {
 
             match values[0] {
                 "d" => service_card_id = Some(values[1].to_string()),
-                "s" => status = Some(values[1].parse()?),
+                "s" => match values.get(2) {
+                    // Skip a bad per-capability entry; the overall status still counts
+                    Some(capability_id) => {
+                        if let Ok(status) = values[1].parse() {
+                            capabilities.insert(capability_id.to_string(), status);
+                        }
+                    }
+                    None => status = Some(values[1].parse()?),
+                },
+                // Details are advisory: drop a malformed one, keep the heartbeat
+                "expiration" => expiration = parse_timestamp("expiration", values[1]).ok(),
+                "load" => load = values[1].parse().ok().filter(|load| *load <= 100),
+                "queue" => queue_depth = values[1].parse().ok(),
+                "capacity" => capacity = values[1].parse().ok(),
+                "version" => version = Some(values[1].to_string()),
+                "uptime" => uptime = values[1].parse().ok(),
+                "message" => message = Some(values[1].to_string()),
+                "return" => return_at = parse_timestamp("return", values[1]).ok(),
                 _ => {}
             }
         }
@@ -113,6 +362,98 @@ impl TryFrom<&Event> for Heartbeat {
             service_card_id: service_card_id
                 .ok_or_else(|| Error::ParseError("missing 'd' tag".to_string()))?,
             status: status.ok_or_else(|| Error::ParseError("missing 'status' tag".to_string()))?,
+            expiration,
+            load,
+            queue_depth,
+            capacity,
+            version,
+            uptime,
+            message,
+            return_at,
+            capabilities,
         })
     }
 }
+
+fn parse_number<T: std::str::FromStr>(tag: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::ParseError(format!("invalid '{tag}' value: {value}")))
+}
+
+/// Parse a unix-seconds tag value such as NIP-40 `expiration`.
+pub(crate) fn parse_timestamp(tag: &str, value: &str) -> Result<Timestamp, Error> {
+    parse_number::<u64>(tag, value).map(Timestamp::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn details_round_trip() {
+        let heartbeat = Heartbeat::busy("card")
+            .load(80)
+            .queue_depth(3)
+            .capacity(4)
+            .version("1.2.0")
+            .uptime(3600)
+            .message("catching up")
+            .return_at(Timestamp::from(1_900_000_000));
+        let event = heartbeat.to_event(&Keys::generate()).unwrap();
+        assert_eq!(Heartbeat::try_from(&event).unwrap(), heartbeat);
+    }
+
+    #[test]
+    fn malformed_details_are_dropped() {
+        let tags = testing::tags(&[
+            &["d", "card"],
+            &["s", "available"],
+            &["load", "150"],
+            &["queue", "many"],
+            &["capacity", "-1"],
+            &["uptime", "1.5"],
+            &["return", "soon"],
+            &["version", "2.0.0"],
+        ]);
+        let heartbeat = Heartbeat::try_from(&testing::event(KIND_HEARTBEAT, tags)).unwrap();
+        assert_eq!(heartbeat.status, Status::Available);
+        assert_eq!(heartbeat.load, None);
+        assert_eq!(heartbeat.queue_depth, None);
+        assert_eq!(heartbeat.capacity, None);
+        assert_eq!(heartbeat.uptime, None);
+        assert_eq!(heartbeat.return_at, None);
+        assert_eq!(heartbeat.version.as_deref(), Some("2.0.0"));
+    }
+
+    #[test]
+    fn malformed_expiration_is_ignored() {
+        let tags = testing::tags(&[&["d", "card"], &["s", "busy"], &["expiration", "later"]]);
+        let heartbeat = Heartbeat::try_from(&testing::event(KIND_HEARTBEAT, tags)).unwrap();
+        assert_eq!(heartbeat.status, Status::Busy);
+        assert_eq!(heartbeat.expiration, None);
+    }
+
+    #[test]
+    fn malformed_capability_status_is_skipped() {
+        let tags = testing::tags(&[
+            &["d", "card"],
+            &["s", "available"],
+            &["s", "", "text.translation"],
+            &["s", "busy", "text.summarization"],
+        ]);
+        let heartbeat = Heartbeat::try_from(&testing::event(KIND_HEARTBEAT, tags)).unwrap();
+        assert_eq!(heartbeat.status, Status::Available);
+        assert_eq!(
+            heartbeat.capabilities.into_iter().collect::<Vec<_>>(),
+            [("text.summarization".to_string(), Status::Busy)]
+        );
+    }
+
+    #[test]
+    fn missing_status_is_an_error() {
+        let tags = testing::tags(&[&["d", "card"], &["load", "10"]]);
+        assert!(Heartbeat::try_from(&testing::event(KIND_HEARTBEAT, tags)).is_err());
+    }
+}
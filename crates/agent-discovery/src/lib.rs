@@ -5,6 +5,7 @@
 //! # Features
 //!
 //! - `relay` - Enable relay communication (requires async runtime)
+//! - `cache` - Local SQLite cache of discovery events
 //!
 //! # Usage (Rust with relay feature)
 //!
@@ -13,38 +14,88 @@
 //! client.connect(&["wss://relay.damus.io"]).await?;
 //! client.publish_service_card(&card).await?;
 //! client.send_heartbeat("my-agent", Status::Available).await?;
-//! let agents = client.discover_agents(50, None).await?;
+//! let agents = client.discover_agents(&DiscoveryFilter::new(50)).await?;
 //! client.disconnect().await?;
 //! ```
 
+pub mod budget;
+pub mod deprecation;
 pub mod error;
+pub mod filter;
 pub mod heartbeat;
+pub mod overflow;
+pub mod policy;
+pub mod presence;
+pub mod pricing;
+pub mod schedule;
 pub mod service_card;
+pub mod vocabulary;
 
 #[cfg(feature = "relay")]
 pub mod relay;
 
+#[cfg(feature = "cache")]
+pub mod cache;
+
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
+#[cfg(test)]
+mod testing;
+
+pub use budget::{BudgetWarning, EventSize, RelayLimits};
+pub use deprecation::{ChainEnd, Deprecation, SuccessorChain};
 pub use error::Error;
+pub use filter::DiscoveryFilter;
 pub use heartbeat::{Heartbeat, Status};
-pub use service_card::{Capability, Protocol, ServiceCard};
+pub use overflow::OverflowPart;
+pub use policy::{Policy, Retention, TrainingUse};
+pub use presence::Presence;
+pub use pricing::{PaymentMethod, PriceUnit, Pricing};
+pub use schedule::{AvailabilityWindow, MaintenanceWindow, Weekday};
+pub use service_card::{
+    capability_matches, Capability, CapabilityRequirement, Protocol, ServiceCard, Warning,
+};
+pub use vocabulary::KnownCapability;
 
 #[cfg(feature = "relay")]
 pub use relay::AgentDiscoveryClient;
 
+#[cfg(feature = "cache")]
+pub use cache::DiscoveryCache;
+
 /// Event kind for Agent Service Card (parameterized replaceable)
 pub const KIND_SERVICE_CARD: u16 = 31990;
 
+/// Event kind for capabilities split off an oversized service card
+pub const KIND_SERVICE_CARD_OVERFLOW: u16 = 31992;
+
 /// Event kind for Agent Heartbeat (ephemeral)
 pub const KIND_HEARTBEAT: u16 = 31991; // Parameterized replaceable (was 21990 ephemeral)
 
+/// Expected interval between heartbeats, in seconds
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 600;
+
+/// Heartbeats expire (NIP-40) after this many missed intervals
+pub const HEARTBEAT_EXPIRATION_INTERVALS: u64 = 3;
+
+/// Maximum size of a service card's content body, in bytes
+pub const MAX_CONTENT_BYTES: usize = 16 * 1024;
+
+/// An agent whose latest heartbeat is older than this is considered offline
+pub const ONLINE_WINDOW_SECS: u64 = 900;
+
 /// NIP-32 label namespace
 pub const LABEL_NAMESPACE: &str = "agent-discovery";
 
 /// NIP-32 label for service cards
 pub const LABEL_SERVICE_CARD: &str = "service-card";
 
+/// NIP-32 label for service card overflow events
+pub const LABEL_SERVICE_CARD_OVERFLOW: &str = "service-card-overflow";
+
 /// NIP-32 label for heartbeats
 pub const LABEL_HEARTBEAT: &str = "heartbeat";
+
+/// NIP-32 namespace for the human languages an agent works in
+pub const LABEL_LANGUAGE_NAMESPACE: &str = "ISO-639-1";
@@ -0,0 +1,391 @@
+//! Overflow events
+//!
+//! A card with more capabilities than relays accept in one event is split:
+//! the root card keeps what fits and carries `["overflow", "<n>"]`, the rest
+//! go into `n` linked events that point back at the root with an `a` tag.
+
+use std::future::Future;
+
+use nostr::event::{Event, EventBuilder, Kind};
+use nostr::nips::nip01::Coordinate;
+use nostr::{PublicKey, Tag};
+
+use crate::budget::EventSize;
+use crate::service_card::ancestor_tags;
+use crate::{
+    Capability, DiscoveryFilter, Error, ServiceCard, KIND_SERVICE_CARD, KIND_SERVICE_CARD_OVERFLOW,
+    LABEL_NAMESPACE, LABEL_SERVICE_CARD_OVERFLOW,
+};
+
+/// Marker on the `a` tag that points at the root card
+pub const ROOT_MARKER: &str = "root";
+
+/// x-coordinate of the secp256k1 generator, used when only the size of a key matters
+const PLACEHOLDER_PUBKEY: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+/// Capabilities of a service card that didn't fit into the card event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverflowPart {
+    pub card_id: String,
+    /// Position among the card's overflow events, from 0
+    pub index: u32,
+    pub capabilities: Vec<Capability>,
+}
+
+impl OverflowPart {
+    pub fn new(card_id: impl Into<String>, index: u32) -> Self {
+        Self {
+            card_id: card_id.into(),
+            index,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Coordinate of the root card when published by `author`.
+    pub fn root(&self, author: PublicKey) -> Coordinate {
+        Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), author).identifier(&self.card_id)
+    }
+
+    /// Estimated size on the wire. Any author key has the same length.
+    pub fn size_estimate(&self) -> EventSize {
+        let placeholder = PublicKey::from_hex(PLACEHOLDER_PUBKEY).expect("valid x-only key");
+        EventSize::estimate(&self.to_tags(placeholder), "")
+    }
+
+    pub fn to_tags(&self, author: PublicKey) -> Vec<Tag> {
+        let root = self.root(author).to_string();
+        let mut tags = vec![
+            Tag::parse(["L", LABEL_NAMESPACE]).unwrap(),
+            Tag::parse(["l", LABEL_SERVICE_CARD_OVERFLOW, LABEL_NAMESPACE]).unwrap(),
+            Tag::parse(["d", &format!("{}:{}", self.card_id, self.index)]).unwrap(),
+            Tag::parse(["a", &root, "", ROOT_MARKER]).unwrap(),
+        ];
+        for cap in &self.capabilities {
+            tags.push(cap.to_tag());
+        }
+        tags.extend(ancestor_tags(&self.capabilities));
+        tags
+    }
+
+    pub fn to_event_builder(&self, author: PublicKey) -> EventBuilder {
+        EventBuilder::new(Kind::Custom(KIND_SERVICE_CARD_OVERFLOW), "").tags(self.to_tags(author))
+    }
+}
+
+impl TryFrom<&Event> for OverflowPart {
+    type Error = Error;
+
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let mut part = None;
+        let mut capabilities = Vec::new();
+
+        for tag in event.tags.iter() {
+            let values: Vec<&str> = tag.as_slice().iter().map(|s| s.as_str()).collect();
+            if values.is_empty() {
+                continue;
+            }
+
+            match values[0] {
+                "d" if values.len() >= 2 => {
+                    let d = values[1];
+                    let (card_id, index) = d
+                        .rsplit_once(':')
+                        .and_then(|(id, index)| Some((id, index.parse().ok()?)))
+                        .ok_or_else(|| Error::ParseError(format!("invalid overflow id: {d}")))?;
+                    part = Some(OverflowPart::new(card_id, index));
+                }
+                "c" if values.len() >= 3 => {
+                    capabilities.push(Capability::from_tag_values(&values));
+                }
+                _ => {}
+            }
+        }
+
+        let mut part = part.ok_or_else(|| Error::ParseError("missing 'd' tag".to_string()))?;
+        part.capabilities = capabilities;
+        Ok(part)
+    }
+}
+
+/// The cards among discovery results that match `filter`.
+///
+/// Results hold cards and overflow events. Split cards are completed with
+/// the parts `overflow_parts(author, card_id)` returns, and roots matched only
+/// through an overflow event are looked up with `card_at`, which must return
+/// them merged.
+pub async fn assemble_cards<P, PF, C, CF>(
+    events: impl IntoIterator<Item = Event>,
+    filter: &DiscoveryFilter,
+    mut overflow_parts: P,
+    mut card_at: C,
+) -> Result<Vec<(ServiceCard, Event)>, Error>
+where
+    P: FnMut(PublicKey, String) -> PF,
+    PF: Future<Output = Result<Vec<OverflowPart>, Error>>,
+    C: FnMut(Coordinate) -> CF,
+    CF: Future<Output = Result<Option<(ServiceCard, Event)>, Error>>,
+{
+    let mut cards = Vec::new();
+    let mut overflow_roots: Vec<Coordinate> = Vec::new();
+    for event in events {
+        if event.kind == Kind::Custom(KIND_SERVICE_CARD_OVERFLOW) {
+            match OverflowPart::try_from(&event) {
+                Ok(part) => {
+                    let root = part.root(event.pubkey);
+                    if !overflow_roots.contains(&root) {
+                        overflow_roots.push(root);
+                    }
+                }
+                Err(e) => eprintln!("Failed to parse overflow event: {e}"),
+            }
+            continue;
+        }
+
+        match ServiceCard::try_from(&event) {
+            // Relays that ignore NIP-40 may still serve expired cards
+            Ok(card) if card.is_expired() => {}
+            Ok(_) if !event.id.check_pow(filter.min_pow) => {}
+            Ok(card) => cards.push((card, event)),
+            Err(e) => eprintln!("Failed to parse service card: {e}"),
+        }
+    }
+
+    for (card, event) in &mut cards {
+        if card.overflow.is_some() {
+            let parts = overflow_parts(event.pubkey, card.id.clone()).await?;
+            card.merge_overflow(parts);
+        }
+    }
+    for root in overflow_roots {
+        let known = cards
+            .iter()
+            .any(|(card, event)| event.pubkey == root.public_key && card.id == root.identifier);
+        if !known {
+            match card_at(root).await? {
+                Some((_, event)) if !event.id.check_pow(filter.min_pow) => {}
+                Some(found) => cards.push(found),
+                None => {}
+            }
+        }
+    }
+
+    let requirements = filter.requirements()?;
+    if !requirements.is_empty() {
+        cards.retain(|(card, _)| requirements.iter().any(|req| req.is_satisfied_by(card)));
+    }
+    cards.retain(|(card, _)| filter.languages.iter().all(|lang| card.speaks(lang)));
+
+    Ok(cards)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::ready;
+
+    use nostr::Keys;
+
+    use super::*;
+    use crate::{testing, RelayLimits};
+
+    /// At most 12 tags per event: the root keeps a few capabilities.
+    const TIGHT: RelayLimits = RelayLimits {
+        max_event_tags: Some(12),
+        ..RelayLimits::UNLIMITED
+    };
+
+    fn big_card(capabilities: usize) -> ServiceCard {
+        (0..capabilities)
+            .fold(ServiceCard::builder("big", "Big Agent"), |builder, i| {
+                builder.capability(format!("tool.t{i}"), format!("Tool {i}"))
+            })
+            .build()
+    }
+
+    #[test]
+    fn split_then_merge_restores_the_card() {
+        let card = big_card(20);
+        let (mut root, parts) = card.split(&TIGHT).unwrap();
+
+        assert!(parts.len() > 1);
+        assert_eq!(root.overflow, Some(parts.len() as u32));
+        assert!(TIGHT.fits(&root.size_estimate()));
+        for (index, part) in parts.iter().enumerate() {
+            assert_eq!(part.index, index as u32);
+            assert!(TIGHT.fits(&part.size_estimate()));
+        }
+
+        root.merge_overflow(parts);
+        assert_eq!(root, card);
+    }
+
+    #[test]
+    fn small_card_is_not_split() {
+        let card = big_card(2);
+        let (root, parts) = card.split(&TIGHT).unwrap();
+        assert!(parts.is_empty());
+        assert_eq!(root, card);
+    }
+
+    #[test]
+    fn parts_round_trip_through_events() {
+        let keys = Keys::generate();
+        let card = big_card(20);
+        let (root, parts) = card.split(&TIGHT).unwrap();
+
+        let event = root.to_event(&keys).unwrap();
+        let mut merged = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(merged.overflow, root.overflow);
+
+        let parsed: Vec<OverflowPart> = parts
+            .iter()
+            .map(|part| {
+                let event = part.to_event_builder(keys.public_key()).sign_with_keys(&keys).unwrap();
+                assert!(TIGHT.fits(&EventSize::of(&event)));
+                OverflowPart::try_from(&event).unwrap()
+            })
+            .collect();
+        assert_eq!(parsed, parts);
+        assert_eq!(parsed[0].root(keys.public_key()), card.coordinate(keys.public_key()));
+
+        merged.merge_overflow(parsed);
+        assert_eq!(merged.overflow, None);
+        assert_eq!(merged.capabilities, card.capabilities);
+    }
+
+    #[test]
+    fn repeated_index_is_merged_once() {
+        let card = big_card(20);
+        let (mut root, parts) = card.split(&TIGHT).unwrap();
+
+        let mut repeated = parts.clone();
+        repeated.insert(0, parts[0].clone());
+        repeated.push(parts[parts.len() - 1].clone());
+        root.merge_overflow(repeated);
+
+        assert_eq!(root.overflow, None);
+        assert_eq!(root.capabilities, card.capabilities);
+    }
+
+    #[test]
+    fn partial_parts_keep_overflow_set() {
+        let card = big_card(20);
+        let (mut root, mut parts) = card.split(&TIGHT).unwrap();
+        let count = parts.len() as u32;
+        parts.pop();
+
+        root.merge_overflow(parts);
+        assert_eq!(root.overflow, Some(count));
+        assert!(root.capabilities.len() < card.capabilities.len());
+    }
+
+    #[test]
+    fn parts_of_other_cards_are_ignored() {
+        let card = big_card(20);
+        let (mut root, parts) = card.split(&TIGHT).unwrap();
+        let count = root.overflow;
+        let foreign = parts.into_iter().map(|mut part| {
+            part.card_id = "other".to_string();
+            part
+        });
+
+        root.merge_overflow(foreign);
+        assert_eq!(root.overflow, count);
+    }
+
+    #[test]
+    fn single_capability_too_large_is_an_error() {
+        let limits = RelayLimits {
+            max_content_length: None,
+            max_event_tags: None,
+            max_message_length: Some(1_000),
+        };
+        let card = ServiceCard::builder("big", "Big Agent")
+            .capability("text.chat", "Chat")
+            .capability("text.essay", "x".repeat(2_000))
+            .build();
+
+        match card.split(&limits) {
+            Err(Error::EventTooLarge(message)) => assert!(message.contains("text.essay")),
+            other => panic!("expected EventTooLarge, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn oversized_card_without_capabilities_is_an_error() {
+        let limits = RelayLimits {
+            max_content_length: None,
+            max_event_tags: None,
+            max_message_length: Some(1_000),
+        };
+        let card = ServiceCard::builder("big", "Big Agent")
+            .about("x".repeat(2_000))
+            .capability("text.chat", "Chat")
+            .build();
+
+        assert!(matches!(card.split(&limits), Err(Error::EventTooLarge(_))));
+    }
+
+    #[test]
+    fn malformed_part_id_is_an_error() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new(Kind::Custom(KIND_SERVICE_CARD_OVERFLOW), "")
+            .tags([Tag::parse(["d", "no-index"]).unwrap()])
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert!(OverflowPart::try_from(&event).is_err());
+    }
+
+    #[tokio::test]
+    async fn discovery_results_are_reassembled() {
+        let keys = Keys::generate();
+        let card = big_card(20);
+        let (root, parts) = card.split(&TIGHT).unwrap();
+        let root_event = root.to_event(&keys).unwrap();
+        let last_part = parts.last().unwrap();
+        let last_event = testing::event_at(
+            &keys,
+            1_000,
+            KIND_SERVICE_CARD_OVERFLOW,
+            last_part.to_tags(keys.public_key()),
+        );
+        let only_in_overflow = last_part.capabilities[0].id.clone();
+
+        let all = DiscoveryFilter::new(10);
+        let found = assemble_cards(
+            [root_event.clone()],
+            &all,
+            |_, _| ready(Ok(parts.clone())),
+            |_| ready(Ok(None)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, card);
+
+        // Only an overflow event matched, so the root is looked up
+        let filter = DiscoveryFilter::new(10).capability(&only_in_overflow);
+        let found = assemble_cards(
+            [last_event],
+            &filter,
+            |_, _| ready(Ok(Vec::new())),
+            |coordinate| {
+                assert_eq!(coordinate, root.coordinate(keys.public_key()));
+                ready(Ok(Some((card.clone(), root_event.clone()))))
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(found.len(), 1);
+
+        let filter = DiscoveryFilter::new(10).min_pow(255);
+        let found = assemble_cards(
+            [root_event.clone()],
+            &filter,
+            |_, _| ready(Ok(parts.clone())),
+            |_| ready(Ok(None)),
+        )
+        .await
+        .unwrap();
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Operational policy
+//!
+//! Data-handling terms an agent declares so clients can decide whether they
+//! may send it data at all.
+
+use nostr::Tag;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Terms, privacy and data-handling declarations of a service card.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Terms of service URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms: Option<String>,
+
+    /// Privacy policy URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<Retention>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub training: Option<TrainingUse>,
+
+    /// Jurisdictions the agent operates in, e.g. `EU`, `DE` or `US`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self == &Policy::default()
+    }
+
+    /// Whether the agent declares regions and all of them are in `allowed`.
+    ///
+    /// Codes are compared as-is (case-insensitively); `EU` does not imply `DE`.
+    pub fn is_within_regions(&self, allowed: &[&str]) -> bool {
+        !self.regions.is_empty()
+            && self
+                .regions
+                .iter()
+                .all(|region| allowed.iter().any(|a| a.eq_ignore_ascii_case(region)))
+    }
+
+    /// Whether the policy meets a client's compliance requirements.
+    ///
+    /// Undeclared terms count as not acceptable: with `require_retention` the
+    /// agent must declare a retention period, and with non-empty
+    /// `allowed_regions` it must declare regions that all fall within them.
+    pub fn satisfies(&self, require_retention: bool, allowed_regions: &[&str]) -> bool {
+        (!require_retention || self.retention.is_some())
+            && (allowed_regions.is_empty() || self.is_within_regions(allowed_regions))
+    }
+
+    /// Check URLs and region codes.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (name, url) in [("terms", &self.terms), ("privacy", &self.privacy)] {
+            if let Some(url) = url {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(Error::ParseError(format!("{name} URL must be http(s): {url}")));
+                }
+            }
+        }
+
+        if let Some(region) = self.regions.iter().find(|r| !is_region_code(r)) {
+            return Err(Error::ParseError(format!("invalid region code: {region}")));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_tags(&self) -> Vec<Tag> {
+        let mut tags = Vec::new();
+        if let Some(url) = &self.terms {
+            tags.push(Tag::parse(["tos", url]).unwrap());
+        }
+        if let Some(url) = &self.privacy {
+            tags.push(Tag::parse(["privacy", url]).unwrap());
+        }
+        if let Some(retention) = &self.retention {
+            tags.push(Tag::parse(["retention", &retention.to_string()]).unwrap());
+        }
+        if let Some(training) = &self.training {
+            tags.push(Tag::parse(["training", &training.to_string()]).unwrap());
+        }
+        for region in &self.regions {
+            tags.push(Tag::parse(["region", region]).unwrap());
+        }
+        tags
+    }
+}
+
+/// How long submitted data is kept.
+///
+/// Encoded as `none`, `<days>d` or `indefinite`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "period", rename_all = "kebab-case")]
+pub enum Retention {
+    /// Discarded once the job completes
+    None,
+
+    Days { days: u32 },
+
+    Indefinite,
+}
+
+impl std::fmt::Display for Retention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Retention::None => write!(f, "none"),
+            Retention::Days { days } => write!(f, "{days}d"),
+            Retention::Indefinite => write!(f, "indefinite"),
+        }
+    }
+}
+
+impl std::str::FromStr for Retention {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Retention::None),
+            "indefinite" => Ok(Retention::Indefinite),
+            _ => {
+                let days = s
+                    .strip_suffix('d')
+                    .and_then(|days| days.parse().ok())
+                    .ok_or_else(|| Error::ParseError(format!("invalid retention: {s}")))?;
+                Ok(Retention::Days { days })
+            }
+        }
+    }
+}
+
+/// Whether submitted data may be used to train models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrainingUse {
+    /// Never used for training
+    No,
+
+    /// Only with the requester's explicit consent
+    OptIn,
+
+    /// Used unless the requester opts out
+    OptOut,
+
+    Yes,
+}
+
+impl std::fmt::Display for TrainingUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrainingUse::No => write!(f, "no"),
+            TrainingUse::OptIn => write!(f, "opt-in"),
+            TrainingUse::OptOut => write!(f, "opt-out"),
+            TrainingUse::Yes => write!(f, "yes"),
+        }
+    }
+}
+
+impl std::str::FromStr for TrainingUse {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(TrainingUse::No),
+            "opt-in" => Ok(TrainingUse::OptIn),
+            "opt-out" => Ok(TrainingUse::OptOut),
+            "yes" => Ok(TrainingUse::Yes),
+            other => Err(Error::ParseError(format!("unknown training use: {other}"))),
+        }
+    }
+}
+
+/// Uppercase ISO 3166-1 alpha-2 code, optionally with a subdivision (`US-CA`),
+/// or a bloc such as `EU`.
+fn is_region_code(s: &str) -> bool {
+    let (country, subdivision) = match s.split_once('-') {
+        Some((country, sub)) => (country, Some(sub)),
+        None => (s, None),
+    };
+    country.len() == 2
+        && country.bytes().all(|b| b.is_ascii_uppercase())
+        && subdivision.is_none_or(|sub| {
+            (1..=3).contains(&sub.len()) && sub.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(retention: Option<Retention>, regions: &[&str]) -> Policy {
+        Policy {
+            retention,
+            regions: regions.iter().map(|r| r.to_string()).collect(),
+            ..Policy::default()
+        }
+    }
+
+    #[test]
+    fn region_codes() {
+        for code in ["DE", "EU", "US-CA", "GB-ENG", "FR-75"] {
+            assert!(is_region_code(code), "{code}");
+        }
+        for code in ["", "de", "DEU", "D", "US-", "US-CALI", "US-C A", "US_CA"] {
+            assert!(!is_region_code(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn validate_checks_urls_and_regions() {
+        let mut policy = policy(None, &["EU", "US-CA"]);
+        policy.terms = Some("https://example.com/tos".to_string());
+        policy.privacy = Some("http://example.com/privacy".to_string());
+        assert!(policy.validate().is_ok());
+
+        let mut bad_url = policy.clone();
+        bad_url.privacy = Some("example.com/privacy".to_string());
+        assert!(bad_url.validate().is_err());
+
+        let mut bad_region = policy.clone();
+        bad_region.regions.push("europe".to_string());
+        assert!(bad_region.validate().is_err());
+    }
+
+    #[test]
+    fn retention_round_trips() {
+        for retention in [
+            Retention::None,
+            Retention::Days { days: 30 },
+            Retention::Indefinite,
+        ] {
+            assert_eq!(
+                retention.to_string().parse::<Retention>().unwrap(),
+                retention
+            );
+        }
+        assert!("30".parse::<Retention>().is_err());
+        assert!("forever".parse::<Retention>().is_err());
+    }
+
+    #[test]
+    fn undeclared_retention_fails_the_requirement() {
+        assert!(policy(None, &[]).satisfies(false, &[]));
+        assert!(!policy(None, &[]).satisfies(true, &[]));
+        assert!(policy(Some(Retention::None), &[]).satisfies(true, &[]));
+    }
+
+    #[test]
+    fn regions_must_all_be_allowed() {
+        let allowed = ["EU", "ch"];
+        assert!(policy(None, &["EU"]).satisfies(false, &allowed));
+        assert!(policy(None, &["eu", "CH"]).satisfies(false, &allowed));
+        assert!(!policy(None, &["EU", "US"]).satisfies(false, &allowed));
+        // Undeclared regions are excluded, and a bloc doesn't cover its members
+        assert!(!policy(None, &[]).satisfies(false, &allowed));
+        assert!(!policy(None, &["DE"]).satisfies(false, &allowed));
+        assert!(policy(None, &["US"]).satisfies(false, &[]));
+    }
+}
@@ -0,0 +1,213 @@
+//! Presence evaluation
+//!
+//! Combines an agent's latest heartbeat with the schedule on its service card
+//! to tell "off by design" apart from "should be up but isn't".
+
+use nostr::Timestamp;
+use serde::Serialize;
+
+use crate::{Heartbeat, ServiceCard, Status, ONLINE_WINDOW_SECS};
+
+/// Where an agent stands right now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Presence {
+    /// Fresh heartbeat; the status says how available the agent is
+    Online { status: Status },
+
+    /// In a planned maintenance window, or announced maintenance
+    Maintenance { until: Option<Timestamp> },
+
+    /// Outside the card's scheduled hours
+    OffHours { next_available: Option<Timestamp> },
+
+    /// The agent announced it is offline
+    Offline,
+
+    /// No fresh heartbeat while the agent should be up
+    UnexpectedlyOffline,
+}
+
+impl Presence {
+    /// Evaluate presence at `now` from the card and the latest heartbeat with its `created_at`.
+    pub fn evaluate(
+        card: &ServiceCard,
+        heartbeat: Option<(&Heartbeat, Timestamp)>,
+        now: Timestamp,
+    ) -> Self {
+        let fresh = heartbeat.filter(|(hb, created_at)| {
+            let age = now.as_u64().saturating_sub(created_at.as_u64());
+            !hb.is_expired() && age < ONLINE_WINDOW_SECS
+        });
+
+        if let Some((hb, _)) = fresh {
+            return match &hb.status {
+                Status::Maintenance => Presence::Maintenance {
+                    until: hb.return_at.or_else(|| card.maintenance_end(now)),
+                },
+                Status::Offline => Presence::Offline,
+                status => Presence::Online {
+                    status: status.clone(),
+                },
+            };
+        }
+
+        if let Some(until) = card.maintenance_end(now) {
+            return Presence::Maintenance { until: Some(until) };
+        }
+        if !card.is_scheduled_at(now) {
+            return Presence::OffHours {
+                next_available: card.next_scheduled(now),
+            };
+        }
+        Presence::UnexpectedlyOffline
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(self, Presence::Online { .. })
+    }
+}
+
+impl std::fmt::Display for Presence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Presence::Online { status } => write!(f, "{status}"),
+            Presence::Maintenance { .. } => write!(f, "maintenance"),
+            Presence::OffHours { .. } => write!(f, "scheduled off-hours"),
+            Presence::Offline => write!(f, "offline"),
+            Presence::UnexpectedlyOffline => write!(f, "unexpectedly offline"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AvailabilityWindow, MaintenanceWindow};
+
+    /// Monday 2024-01-01 00:00 UTC
+    const MONDAY: u64 = 1_704_067_200;
+
+    /// `hh:mm` UTC on that Monday.
+    fn at(hh: u64, mm: u64) -> Timestamp {
+        Timestamp::from(MONDAY + hh * 3_600 + mm * 60)
+    }
+
+    /// Up 09:00-17:00 on weekdays, with maintenance 12:00-13:00 on Monday.
+    fn office_card() -> ServiceCard {
+        ServiceCard::builder("agent", "Agent")
+            .availability(AvailabilityWindow::parse("mon-fri", "09:00-17:00", None).unwrap())
+            .maintenance(MaintenanceWindow {
+                start: at(12, 0),
+                end: at(13, 0),
+                reason: None,
+            })
+            .build()
+    }
+
+    #[test]
+    fn fresh_heartbeat_reports_its_status() {
+        let card = office_card();
+        let busy = Heartbeat::busy("agent");
+        let presence = Presence::evaluate(&card, Some((&busy, at(10, 0))), at(10, 5));
+        assert_eq!(
+            presence,
+            Presence::Online {
+                status: Status::Busy
+            }
+        );
+
+        // An announced status wins over the schedule
+        let presence = Presence::evaluate(&card, Some((&busy, at(20, 0))), at(20, 5));
+        assert!(presence.is_online());
+
+        let offline = Heartbeat::offline("agent");
+        let presence = Presence::evaluate(&card, Some((&offline, at(10, 0))), at(10, 5));
+        assert_eq!(presence, Presence::Offline);
+    }
+
+    #[test]
+    fn heartbeat_goes_stale_after_the_online_window() {
+        let card = office_card();
+        let hb = Heartbeat::available("agent");
+        let sent = at(10, 0);
+        let last_fresh = Timestamp::from(sent.as_u64() + ONLINE_WINDOW_SECS - 1);
+        let stale = Timestamp::from(sent.as_u64() + ONLINE_WINDOW_SECS);
+
+        assert!(Presence::evaluate(&card, Some((&hb, sent)), last_fresh).is_online());
+        assert_eq!(
+            Presence::evaluate(&card, Some((&hb, sent)), stale),
+            Presence::UnexpectedlyOffline
+        );
+    }
+
+    #[test]
+    fn silence_outside_scheduled_hours_is_off_hours() {
+        let card = office_card();
+        assert_eq!(
+            Presence::evaluate(&card, None, at(8, 59)),
+            Presence::OffHours {
+                next_available: Some(at(9, 0))
+            }
+        );
+        assert_eq!(
+            Presence::evaluate(&card, None, at(9, 0)),
+            Presence::UnexpectedlyOffline
+        );
+        assert_eq!(
+            Presence::evaluate(&card, None, at(16, 59)),
+            Presence::UnexpectedlyOffline
+        );
+        assert!(matches!(
+            Presence::evaluate(&card, None, at(17, 0)),
+            Presence::OffHours { .. }
+        ));
+    }
+
+    #[test]
+    fn card_without_schedule_is_always_expected_up() {
+        let card = ServiceCard::builder("agent", "Agent").build();
+        assert_eq!(
+            Presence::evaluate(&card, None, at(3, 0)),
+            Presence::UnexpectedlyOffline
+        );
+    }
+
+    #[test]
+    fn planned_maintenance_covers_silence() {
+        let card = office_card();
+        let maintenance = Presence::Maintenance {
+            until: Some(at(13, 0)),
+        };
+        assert_eq!(
+            Presence::evaluate(&card, None, at(11, 59)),
+            Presence::UnexpectedlyOffline
+        );
+        assert_eq!(Presence::evaluate(&card, None, at(12, 0)), maintenance);
+        assert_eq!(Presence::evaluate(&card, None, at(12, 59)), maintenance);
+        assert_eq!(
+            Presence::evaluate(&card, None, at(13, 0)),
+            Presence::UnexpectedlyOffline
+        );
+    }
+
+    #[test]
+    fn maintenance_heartbeat_falls_back_to_the_planned_end() {
+        let card = office_card();
+        let hb = Heartbeat::maintenance("agent");
+        assert_eq!(
+            Presence::evaluate(&card, Some((&hb, at(12, 10))), at(12, 15)),
+            Presence::Maintenance {
+                until: Some(at(13, 0))
+            }
+        );
+
+        let hb = Heartbeat::maintenance("agent").return_at(at(14, 0));
+        assert_eq!(
+            Presence::evaluate(&card, Some((&hb, at(12, 10))), at(12, 15)),
+            Presence::Maintenance {
+                until: Some(at(14, 0))
+            }
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Pricing and payment terms
+//!
+//! Amounts are in millisats, matching Lightning and NIP-57 zaps.
+
+use nostr::Tag;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// What an agent charges for a job.
+///
+/// Encoded as a compact string: `free`, `flat:<msats>`,
+/// `per-<unit>:<msats>` or `quote`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "model", rename_all = "kebab-case")]
+pub enum Pricing {
+    Free,
+
+    /// Fixed amount per job
+    Flat { msats: u64 },
+
+    /// Amount per unit of input or output
+    PerUnit { msats: u64, unit: PriceUnit },
+
+    /// Price is negotiated per request
+    Quote,
+}
+
+impl Pricing {
+    /// Whether the price is known to be at most `max_msats`.
+    ///
+    /// Per-unit prices are compared per unit. Quotes are never within a
+    /// budget since the price is not known up front.
+    pub fn is_within(&self, max_msats: u64) -> bool {
+        match self {
+            Pricing::Free => true,
+            Pricing::Flat { msats } | Pricing::PerUnit { msats, .. } => *msats <= max_msats,
+            Pricing::Quote => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Pricing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pricing::Free => write!(f, "free"),
+            Pricing::Flat { msats } => write!(f, "flat:{msats}"),
+            Pricing::PerUnit { msats, unit } => write!(f, "per-{unit}:{msats}"),
+            Pricing::Quote => write!(f, "quote"),
+        }
+    }
+}
+
+impl std::str::FromStr for Pricing {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::ParseError(format!("invalid pricing: {s}"));
+
+        match s.split_once(':') {
+            None if s == "free" => Ok(Pricing::Free),
+            None if s == "quote" => Ok(Pricing::Quote),
+            None => Err(invalid()),
+            Some((model, amount)) => {
+                let msats = amount.parse().map_err(|_| invalid())?;
+                match model {
+                    "flat" => Ok(Pricing::Flat { msats }),
+                    _ => {
+                        let unit = model.strip_prefix("per-").ok_or_else(invalid)?.parse()?;
+                        Ok(Pricing::PerUnit { msats, unit })
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Unit for per-unit pricing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceUnit {
+    Token,
+    Minute,
+    Mb,
+}
+
+impl std::fmt::Display for PriceUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceUnit::Token => write!(f, "token"),
+            PriceUnit::Minute => write!(f, "minute"),
+            PriceUnit::Mb => write!(f, "mb"),
+        }
+    }
+}
+
+impl std::str::FromStr for PriceUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "token" => Ok(PriceUnit::Token),
+            "minute" => Ok(PriceUnit::Minute),
+            "mb" => Ok(PriceUnit::Mb),
+            other => Err(Error::ParseError(format!("unknown price unit: {other}"))),
+        }
+    }
+}
+
+/// How an agent accepts payment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PaymentMethod {
+    /// Lightning address (LUD-16), e.g. `agent@getalby.com`
+    Lightning { address: String },
+
+    /// NIP-57 zaps to the agent's pubkey
+    Zap,
+
+    /// BOLT12 offer
+    Bolt12 { offer: String },
+}
+
+impl PaymentMethod {
+    pub fn id(&self) -> &str {
+        match self {
+            PaymentMethod::Lightning { .. } => "lightning",
+            PaymentMethod::Zap => "zap",
+            PaymentMethod::Bolt12 { .. } => "bolt12",
+        }
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        match self {
+            PaymentMethod::Lightning { address } => {
+                Tag::parse(["payment", self.id(), address]).unwrap()
+            }
+            PaymentMethod::Zap => Tag::parse(["payment", self.id()]).unwrap(),
+            PaymentMethod::Bolt12 { offer } => Tag::parse(["payment", self.id(), offer]).unwrap(),
+        }
+    }
+
+    /// Parse from a method id and its value, as in `["payment", <id>, <value>]`.
+    pub fn parse(id: &str, value: Option<&str>) -> Result<Self, Error> {
+        let missing = || Error::ParseError(format!("payment method '{id}' requires a value"));
+
+        match id {
+            "lightning" => Ok(PaymentMethod::Lightning {
+                address: value.ok_or_else(missing)?.to_string(),
+            }),
+            "zap" => Ok(PaymentMethod::Zap),
+            "bolt12" => Ok(PaymentMethod::Bolt12 {
+                offer: value.ok_or_else(missing)?.to_string(),
+            }),
+            other => Err(Error::ParseError(format!("unknown payment method: {other}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pricing_round_trips() {
+        for pricing in [
+            Pricing::Free,
+            Pricing::Flat { msats: 21_000 },
+            Pricing::PerUnit {
+                msats: 5,
+                unit: PriceUnit::Token,
+            },
+            Pricing::PerUnit {
+                msats: 1_000,
+                unit: PriceUnit::Minute,
+            },
+            Pricing::Quote,
+        ] {
+            assert_eq!(pricing.to_string().parse::<Pricing>().unwrap(), pricing);
+        }
+        assert_eq!("per-mb:7".parse::<Pricing>().unwrap().to_string(), "per-mb:7");
+    }
+
+    #[test]
+    fn pricing_rejects_unknown_models() {
+        for s in ["", "flat", "flat:abc", "per-second:10", "auction:5", "free:0"] {
+            assert!(s.parse::<Pricing>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn is_within_compares_known_prices() {
+        assert!(Pricing::Free.is_within(0));
+        assert!(Pricing::Flat { msats: 1_000 }.is_within(1_000));
+        assert!(!Pricing::Flat { msats: 1_001 }.is_within(1_000));
+        let per_token = Pricing::PerUnit {
+            msats: 5,
+            unit: PriceUnit::Token,
+        };
+        assert!(per_token.is_within(5));
+        assert!(!per_token.is_within(4));
+        assert!(!Pricing::Quote.is_within(u64::MAX));
+    }
+
+    #[test]
+    fn payment_methods_parse_from_tags() {
+        let lightning = PaymentMethod::parse("lightning", Some("agent@example.com")).unwrap();
+        assert_eq!(
+            lightning,
+            PaymentMethod::Lightning {
+                address: "agent@example.com".to_string()
+            }
+        );
+        assert_eq!(
+            lightning.to_tag().as_slice(),
+            ["payment", "lightning", "agent@example.com"]
+        );
+        assert_eq!(PaymentMethod::parse("zap", None).unwrap(), PaymentMethod::Zap);
+        assert!(PaymentMethod::parse("bolt12", None).is_err());
+        assert!(PaymentMethod::parse("ecash", Some("token")).is_err());
+    }
+}
@@ -2,27 +2,98 @@
 //!
 //! Feature-gated behind `relay` feature. Excluded from WASM builds.
 
-use std::time::Duration;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+use nostr::nips::nip11::{Limitation, RelayInformationDocument};
+use nostr::nips::nip65::{self, RelayMetadata};
 use nostr_sdk::prelude::*;
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::task::{JoinHandle, JoinSet};
 
+use crate::budget::EventSize;
+use crate::overflow::assemble_cards;
+use crate::service_card::primary_language;
 use crate::{
-    Error, Heartbeat, ServiceCard, Status, KIND_HEARTBEAT, KIND_SERVICE_CARD, LABEL_NAMESPACE,
+    vocabulary, DiscoveryFilter, Error, Heartbeat, OverflowPart, RelayLimits, ServiceCard, Status,
+    SuccessorChain, KIND_HEARTBEAT, KIND_SERVICE_CARD, KIND_SERVICE_CARD_OVERFLOW, LABEL_NAMESPACE,
 };
 
+/// How long to wait for a relay's NIP-11 document
+const RELAY_INFO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for relays to answer a request
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a relay's NIP-42 AUTH challenge
+const AUTH_CHALLENGE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Machine-readable prefix of relay messages asking for NIP-42 AUTH
+const AUTH_REQUIRED_PREFIX: &str = "auth-required:";
+
+/// Relays taken from an agent's outbox before the rest are ignored
+pub const MAX_OUTBOX_RELAYS: usize = 8;
+
+/// Cards fetched per author when listing authors' cards
+const MAX_CARDS_PER_AUTHOR: usize = 100;
+
+/// How far before the last sync an incremental cache sync starts, for
+/// events that reached relays late or carry a skewed `created_at`
+#[cfg(feature = "cache")]
+const SYNC_OVERLAP_SECS: u64 = 300;
+
+/// Events requested per page when syncing a cache
+#[cfg(feature = "cache")]
+const SYNC_PAGE_SIZE: usize = 500;
+
 /// Client for agent discovery operations.
 pub struct AgentDiscoveryClient {
     client: Client,
+    public_key: PublicKey,
+    relay_info: RwLock<HashMap<RelayUrl, RelayInformationDocument>>,
+    /// Relays the client answers NIP-42 AUTH challenges from
+    auth_relays: RwLock<HashSet<RelayUrl>>,
+    /// Latest AUTH challenge received from each relay
+    challenges: Arc<RwLock<HashMap<RelayUrl, String>>>,
+    /// Task recording AUTH challenges, started by the first authenticated connect
+    challenge_listener: Mutex<Option<JoinHandle<()>>>,
+    /// NIP-13 difficulty mined into published service cards
+    pow: u8,
 }
 
 impl AgentDiscoveryClient {
     /// Create a new client with the given keys.
+    ///
+    /// The client only authenticates to relays connected with
+    /// [`connect_authenticated`](Self::connect_authenticated).
     pub async fn new(keys: Keys) -> Result<Self, Error> {
-        let client = Client::new(keys);
-        Ok(Self { client })
+        let public_key = keys.public_key();
+        let client = Client::builder()
+            .signer(keys)
+            .opts(Options::new().automatic_authentication(false))
+            .build();
+
+        Ok(Self {
+            client,
+            public_key,
+            relay_info: RwLock::new(HashMap::new()),
+            auth_relays: RwLock::new(HashSet::new()),
+            challenges: Arc::new(RwLock::new(HashMap::new())),
+            challenge_listener: Mutex::new(None),
+            pow: 0,
+        })
     }
 
-    /// Connect to relays.
+    /// Mine NIP-13 proof of work of `difficulty` bits into published service
+    /// cards and their overflow events.
+    pub fn pow_difficulty(mut self, difficulty: u8) -> Self {
+        self.pow = difficulty;
+        self
+    }
+
+    /// Connect to relays and fetch their NIP-11 documents.
     pub async fn connect(&self, relays: &[&str]) -> Result<(), Error> {
         for relay in relays {
             self.client
@@ -31,80 +102,755 @@ impl AgentDiscoveryClient {
                 .map_err(|e| Error::RelayError(e.to_string()))?;
         }
         self.client.connect().await;
+        self.fetch_relay_info().await;
         Ok(())
     }
 
-    /// Disconnect from all relays.
-    pub async fn disconnect(&self) -> Result<(), Error> {
-        let _ = self.client.disconnect().await;
+    /// Connect to relays that require NIP-42 authentication.
+    ///
+    /// The client signs AUTH challenges from these relays with its keys, up
+    /// front if the relay sends a challenge on connect and again whenever it
+    /// rejects an event or closes a request with `auth-required`.
+    pub async fn connect_authenticated(&self, relays: &[&str]) -> Result<(), Error> {
+        let mut urls = Vec::new();
+        for relay in relays {
+            let url = RelayUrl::parse(relay).map_err(|e| Error::RelayError(e.to_string()))?;
+            self.auth_relays
+                .write()
+                .expect("auth relay lock poisoned")
+                .insert(url.clone());
+            urls.push(url);
+        }
+        // Listen before connecting; relays often send a challenge right away
+        self.listen_for_challenges();
+        self.connect(relays).await?;
+
+        for url in &urls {
+            if self.wait_for_challenge(url).await.is_some() {
+                self.authenticate(url).await?;
+            }
+        }
         Ok(())
     }
 
-    /// Publish a service card.
-    pub async fn publish_service_card(&self, card: &ServiceCard) -> Result<EventId, Error> {
-        let builder = EventBuilder::from(card);
+    /// Start recording AUTH challenges, unless already doing so.
+    fn listen_for_challenges(&self) {
+        let mut listener = self
+            .challenge_listener
+            .lock()
+            .expect("listener lock poisoned");
+        if listener.is_some() {
+            return;
+        }
+
+        let mut notifications = self.client.notifications();
+        let challenges = Arc::clone(&self.challenges);
+        *listener = Some(tokio::spawn(async move {
+            while let Ok(notification) = notifications.recv().await {
+                if let RelayPoolNotification::Message {
+                    relay_url,
+                    message: RelayMessage::Auth { challenge },
+                } = notification
+                {
+                    challenges
+                        .write()
+                        .expect("challenge lock poisoned")
+                        .insert(relay_url, challenge);
+                }
+            }
+        }));
+    }
+
+    /// Stop recording AUTH challenges.
+    fn stop_listening(&self) {
+        let listener = self
+            .challenge_listener
+            .lock()
+            .expect("listener lock poisoned")
+            .take();
+        if let Some(listener) = listener {
+            listener.abort();
+        }
+    }
+
+    /// Whether the client authenticates to a relay.
+    pub fn authenticates_to(&self, relay: &RelayUrl) -> bool {
+        self.auth_relays
+            .read()
+            .expect("auth relay lock poisoned")
+            .contains(relay)
+    }
+
+    /// Wait briefly for an AUTH challenge from a relay.
+    async fn wait_for_challenge(&self, url: &RelayUrl) -> Option<String> {
+        let deadline = Instant::now() + AUTH_CHALLENGE_TIMEOUT;
+        loop {
+            let challenge = self
+                .challenges
+                .read()
+                .expect("challenge lock poisoned")
+                .get(url)
+                .cloned();
+            if challenge.is_some() || Instant::now() >= deadline {
+                return challenge;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Answer a relay's latest AUTH challenge.
+    async fn authenticate(&self, url: &RelayUrl) -> Result<(), Error> {
+        let challenge = self
+            .wait_for_challenge(url)
+            .await
+            .ok_or_else(|| Error::AuthError(format!("{url}: no challenge received")))?;
+
+        let event = self
+            .client
+            .sign_event_builder(EventBuilder::auth(challenge, url.clone()))
+            .await
+            .map_err(|e| Error::SigningError(e.to_string()))?;
+        let relay = self
+            .client
+            .relay(url)
+            .await
+            .map_err(|e| Error::RelayError(e.to_string()))?;
+        relay
+            .auth(event)
+            .await
+            .map_err(|e| Error::AuthError(format!("{url}: {e}")))
+    }
+
+    /// Fetch events from the connected relays.
+    async fn fetch(&self, filters: Vec<Filter>) -> Result<Events, Error> {
+        let relays = self.client.relays().await.into_keys().collect();
+        self.fetch_from(relays, filters).await
+    }
+
+    /// Fetch events from some of the connected relays.
+    ///
+    /// Relays we authenticate to that close the request with `auth-required`
+    /// are answered and asked again.
+    async fn fetch_from(
+        &self,
+        relays: Vec<RelayUrl>,
+        filters: Vec<Filter>,
+    ) -> Result<Events, Error> {
+        let mut notifications = self.client.notifications();
+        let events = self
+            .client
+            .fetch_events_from(relays, filters.clone(), Some(FETCH_TIMEOUT))
+            .await
+            .map_err(|e| Error::RelayError(e.to_string()))?;
+
+        let mut retry: HashSet<RelayUrl> = HashSet::new();
+        loop {
+            match notifications.try_recv() {
+                Ok(RelayPoolNotification::Message {
+                    relay_url,
+                    message: RelayMessage::Closed { message, .. },
+                }) if message.starts_with(AUTH_REQUIRED_PREFIX)
+                    && self.authenticates_to(&relay_url) =>
+                {
+                    retry.insert(relay_url);
+                }
+                Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        if retry.is_empty() {
+            return Ok(events);
+        }
+
+        for url in &retry {
+            self.authenticate(url).await?;
+        }
+        let more = self
+            .client
+            .fetch_events_from(retry, filters, Some(FETCH_TIMEOUT))
+            .await
+            .map_err(|e| Error::RelayError(e.to_string()))?;
+        Ok(events.merge(more))
+    }
+
+    /// Fetch NIP-11 documents of relays not yet cached, concurrently.
+    ///
+    /// Relays without a document, or that don't answer in time, are left out.
+    async fn fetch_relay_info(&self) {
+        let relays = self.client.relays().await;
+        let urls: Vec<RelayUrl> = {
+            let cached = self.relay_info.read().expect("relay info lock poisoned");
+            relays.into_keys().filter(|url| !cached.contains_key(url)).collect()
+        };
+
+        // Dropping the set aborts fetches still running
+        let mut fetches = JoinSet::new();
+        for url in urls {
+            fetches.spawn(async move {
+                let http = Url::parse(url.as_str()).ok()?;
+                let fetch = RelayInformationDocument::get(http, None);
+                match tokio::time::timeout(RELAY_INFO_TIMEOUT, fetch).await {
+                    Ok(Ok(doc)) => Some((url, doc)),
+                    _ => None,
+                }
+            });
+        }
+
+        while let Some(fetched) = fetches.join_next().await {
+            if let Ok(Some((url, doc))) = fetched {
+                self.relay_info
+                    .write()
+                    .expect("relay info lock poisoned")
+                    .insert(url, doc);
+            }
+        }
+    }
+
+    /// Cached NIP-11 documents of the connected relays.
+    pub fn relay_info(&self) -> HashMap<RelayUrl, RelayInformationDocument> {
+        self.relay_info.read().expect("relay info lock poisoned").clone()
+    }
+
+    /// Tightest limits advertised by the connected relays, if any advertise some.
+    pub fn relay_limits(&self) -> Option<RelayLimits> {
+        self.relay_info
+            .read()
+            .expect("relay info lock poisoned")
+            .values()
+            .filter_map(|doc| doc.limitation.as_ref())
+            .map(limits_of)
+            .reduce(RelayLimits::min)
+    }
+
+    /// Sign an event, mining `pow` bits of proof of work, and send it to the
+    /// relays whose NIP-11 limitations it meets.
+    async fn send(&self, builder: EventBuilder, pow: u8) -> Result<EventId, Error> {
+        let event = if pow > 0 {
+            // Mining is CPU-bound, keep it off the async executor
+            let public_key = self.public_key;
+            let unsigned = tokio::task::spawn_blocking(move || builder.pow(pow).build(public_key))
+                .await
+                .map_err(|e| Error::SigningError(e.to_string()))?;
+            let signer = self
+                .client
+                .signer()
+                .await
+                .map_err(|e| Error::SigningError(e.to_string()))?;
+            signer
+                .sign_event(unsigned)
+                .await
+                .map_err(|e| Error::SigningError(e.to_string()))?
+        } else {
+            self.client
+                .sign_event_builder(builder)
+                .await
+                .map_err(|e| Error::RelayError(e.to_string()))?
+        };
+
+        let relays = self.client.relays().await;
+        let targets: Vec<RelayUrl> = {
+            let info = self.relay_info.read().expect("relay info lock poisoned");
+            relays
+                .into_keys()
+                .filter(|url| {
+                    let authenticated = self.authenticates_to(url);
+                    let rejection = info
+                        .get(url)
+                        .and_then(|doc| doc.limitation.as_ref())
+                        .and_then(|limitation| rejection(limitation, &event, authenticated));
+                    if let Some(reason) = &rejection {
+                        eprintln!("Skipping {url}: {reason}");
+                    }
+                    rejection.is_none()
+                })
+                .collect()
+        };
+        if targets.is_empty() {
+            return Err(Error::RelayError("no connected relay accepts this event".to_string()));
+        }
+
         let output = self
             .client
-            .send_event_builder(builder)
+            .send_event_to(targets, event.clone())
             .await
             .map_err(|e| Error::RelayError(e.to_string()))?;
+
+        // Relays may only ask for AUTH once we write; answer and send again
+        let retry: Vec<RelayUrl> = output
+            .failed
+            .iter()
+            .filter(|(url, reason)| {
+                let auth_required = reason
+                    .as_deref()
+                    .is_some_and(|reason| reason.starts_with(AUTH_REQUIRED_PREFIX));
+                auth_required && self.authenticates_to(url)
+            })
+            .map(|(url, _)| url.clone())
+            .collect();
+        if !retry.is_empty() {
+            for url in &retry {
+                self.authenticate(url).await?;
+            }
+            self.client
+                .send_event_to(retry, event)
+                .await
+                .map_err(|e| Error::RelayError(e.to_string()))?;
+        }
+
         Ok(output.val)
     }
 
+    /// Connect to the DM relays advertised on an agent's service card.
+    pub async fn connect_dm_relays(&self, card: &ServiceCard) -> Result<(), Error> {
+        let relays = card.dm_relays();
+        if relays.is_empty() {
+            return Err(Error::InvalidProtocol(format!(
+                "service card '{}' advertises no DM relays",
+                card.id
+            )));
+        }
+
+        for relay in relays {
+            self.client
+                .add_relay(relay.to_string())
+                .await
+                .map_err(|e| Error::RelayError(e.to_string()))?;
+        }
+        self.client.connect().await;
+        Ok(())
+    }
+
+    /// Fetch an author's NIP-65 relay list (`kind:10002`), newest version.
+    ///
+    /// Returns an empty list if the author has none.
+    pub async fn get_relay_list(
+        &self,
+        author: PublicKey,
+    ) -> Result<Vec<(RelayUrl, Option<RelayMetadata>)>, Error> {
+        let mut lists = self.get_relay_lists(&[author]).await?;
+        Ok(lists.remove(&author).unwrap_or_default())
+    }
+
+    /// Fetch the NIP-65 relay lists of several authors in one request.
+    ///
+    /// Authors without a list are left out.
+    pub async fn get_relay_lists(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<HashMap<PublicKey, Vec<(RelayUrl, Option<RelayMetadata>)>>, Error> {
+        if authors.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let filter = Filter::new()
+            .kind(Kind::RelayList)
+            .authors(authors.iter().copied());
+
+        let events = self.fetch(vec![filter]).await?;
+
+        // Relays may still hold older versions of the replaceable events
+        let mut newest: HashMap<PublicKey, Event> = HashMap::new();
+        for event in events {
+            match newest.get(&event.pubkey) {
+                Some(known) if known.created_at >= event.created_at => {}
+                _ => {
+                    newest.insert(event.pubkey, event);
+                }
+            }
+        }
+
+        Ok(newest
+            .into_iter()
+            .map(|(author, event)| {
+                let list = nip65::extract_relay_list(&event)
+                    .map(|(url, metadata)| (url.clone(), *metadata))
+                    .collect();
+                (author, list)
+            })
+            .collect())
+    }
+
+    /// Connect to the relays agents publish to: the write relays of the
+    /// `authors`' NIP-65 relay lists plus the DM relays of `cards` already seen.
+    ///
+    /// Returns the relays that weren't connected yet, at most
+    /// [`MAX_OUTBOX_RELAYS`] in total.
+    pub async fn connect_outbox(
+        &self,
+        authors: &[PublicKey],
+        cards: &[ServiceCard],
+    ) -> Result<Vec<RelayUrl>, Error> {
+        let mut outbox: Vec<RelayUrl> = Vec::new();
+        if !authors.is_empty() {
+            for list in self.get_relay_lists(authors).await?.into_values() {
+                outbox.extend(
+                    list.into_iter()
+                        .filter(|(_, metadata)| *metadata != Some(RelayMetadata::Read))
+                        .map(|(url, _)| url),
+                );
+            }
+        }
+        for card in cards {
+            outbox.extend(card.dm_relays().iter().cloned());
+        }
+
+        let connected = self.client.relays().await;
+        let mut added = Vec::new();
+        for url in outbox {
+            if added.len() >= MAX_OUTBOX_RELAYS {
+                break;
+            }
+            if connected.contains_key(&url) || added.contains(&url) {
+                continue;
+            }
+            self.client
+                .add_relay(url.as_str())
+                .await
+                .map_err(|e| Error::RelayError(e.to_string()))?;
+            added.push(url);
+        }
+
+        if !added.is_empty() {
+            self.client.connect().await;
+            self.fetch_relay_info().await;
+        }
+        Ok(added)
+    }
+
+    /// Publish our NIP-65 relay list (`kind:10002`).
+    ///
+    /// Replaces any earlier list, so include every relay we read from or
+    /// write to, not just the agent discovery ones.
+    pub async fn publish_relay_list(
+        &self,
+        relays: &[(RelayUrl, Option<RelayMetadata>)],
+    ) -> Result<EventId, Error> {
+        self.send(EventBuilder::relay_list(relays.iter().cloned()), 0).await
+    }
+
+    /// Disconnect from all relays.
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.stop_listening();
+        let _ = self.client.disconnect().await;
+        Ok(())
+    }
+
+    /// Publish a service card.
+    pub async fn publish_service_card(&self, card: &ServiceCard) -> Result<EventId, Error> {
+        self.send(EventBuilder::from(card), self.pow).await
+    }
+
+    /// Publish a service card, splitting capabilities that exceed `limits`
+    /// into overflow events.
+    ///
+    /// The overflow events are sent first, so relays never hold a root
+    /// announcing parts that failed to publish. Returns the card event id
+    /// followed by the overflow event ids.
+    pub async fn publish_service_card_split(
+        &self,
+        card: &ServiceCard,
+        limits: &RelayLimits,
+    ) -> Result<Vec<EventId>, Error> {
+        let (root, parts) = card.split(limits)?;
+
+        let mut ids = Vec::with_capacity(parts.len() + 1);
+        for part in &parts {
+            let builder = part.to_event_builder(self.public_key);
+            ids.push(self.send(builder, self.pow).await?);
+        }
+        ids.insert(0, self.publish_service_card(&root).await?);
+        Ok(ids)
+    }
+
     /// Send a heartbeat.
     pub async fn send_heartbeat(
         &self,
         service_card_id: &str,
         status: Status,
     ) -> Result<EventId, Error> {
-        let heartbeat = Heartbeat::new(service_card_id, status);
-        let builder = EventBuilder::from(&heartbeat);
-        let output = self
-            .client
-            .send_event_builder(builder)
-            .await
-            .map_err(|e| Error::RelayError(e.to_string()))?;
-        Ok(output.val)
+        self.publish_heartbeat(&Heartbeat::new(service_card_id, status)).await
+    }
+
+    /// Publish a heartbeat carrying optional load, queue and status details.
+    pub async fn publish_heartbeat(&self, heartbeat: &Heartbeat) -> Result<EventId, Error> {
+        self.send(EventBuilder::from(heartbeat), 0).await
     }
 
-    /// Discover agents, optionally filtered by capabilities (AND logic).
+    /// Discover agents matching a filter.
+    ///
+    /// Well-known capability aliases are expanded, so `summarize` also finds
+    /// cards advertising `text.summarization`. Version requirements are
+    /// checked client-side.
+    ///
+    /// Languages are sent to relays as an `#l` filter.
     pub async fn discover_agents(
         &self,
-        limit: usize,
-        author: Option<PublicKey>,
-        capabilities: &[&str],
+        discovery: &DiscoveryFilter,
     ) -> Result<Vec<(ServiceCard, Event)>, Error> {
         let mut filter = Filter::new()
-            .kind(Kind::Custom(KIND_SERVICE_CARD))
             .custom_tag(SingleLetterTag::uppercase(Alphabet::L), [LABEL_NAMESPACE])
-            .limit(limit);
+            .limit(discovery.limit);
 
-        if let Some(pk) = author {
+        if let Some(pk) = discovery.author {
             filter = filter.author(pk);
         }
 
+        let requirements = discovery.requirements()?;
+
         // Multiple capabilities = OR (matches Nostr filter semantics)
-        if !capabilities.is_empty() {
-            filter = filter.custom_tag(SingleLetterTag::lowercase(Alphabet::C), capabilities.to_vec());
+        if !requirements.is_empty() {
+            let expanded: Vec<String> = requirements
+                .iter()
+                .flat_map(|req| vocabulary::expand(&req.id))
+                .collect();
+            filter = filter.custom_tag(SingleLetterTag::lowercase(Alphabet::C), expanded);
         }
 
-        let events = self
-            .client
-            .fetch_events(vec![filter], Some(Duration::from_secs(10)))
-            .await
-            .map_err(|e| Error::RelayError(e.to_string()))?;
+        // Overflow events carry capabilities too, so a `#c` match may only hit
+        // those, but no language labels
+        let card_kind = Kind::Custom(KIND_SERVICE_CARD);
+        let overflow_kind = Kind::Custom(KIND_SERVICE_CARD_OVERFLOW);
+        let filters = if discovery.languages.is_empty() {
+            vec![filter.kinds([card_kind, overflow_kind])]
+        } else {
+            let codes = discovery.languages.iter().map(|lang| primary_language(lang));
+            let mut filters = vec![filter
+                .clone()
+                .kind(card_kind)
+                .custom_tag(SingleLetterTag::lowercase(Alphabet::L), codes)];
+            if !requirements.is_empty() {
+                filters.push(filter.kind(overflow_kind));
+            }
+            filters
+        };
+
+        let events = self.fetch(filters).await?;
+        assemble_cards(
+            events,
+            discovery,
+            |author, card_id| async move { self.get_overflow_parts(author, &card_id).await },
+            |root| async move { self.get_service_card(&root).await },
+        )
+        .await
+    }
+
+    /// Fetch the newest service card at a coordinate.
+    ///
+    /// Returns `None` if no relay has it, or if it is expired or unparseable.
+    pub async fn get_service_card(
+        &self,
+        coordinate: &Coordinate,
+    ) -> Result<Option<(ServiceCard, Event)>, Error> {
+        let filter = Filter::new()
+            .kind(Kind::Custom(KIND_SERVICE_CARD))
+            .author(coordinate.public_key)
+            .custom_tag(
+                SingleLetterTag::lowercase(Alphabet::D),
+                [coordinate.identifier.as_str()],
+            );
+
+        let events = self.fetch(vec![filter]).await?;
+
+        // Relays may still hold older versions of the replaceable event
+        let Some(event) = events.into_iter().max_by_key(|e| e.created_at) else {
+            return Ok(None);
+        };
+        match ServiceCard::try_from(&event) {
+            Ok(card) if card.is_expired() => Ok(None),
+            Ok(mut card) => {
+                self.merge_overflow(&mut card, event.pubkey).await?;
+                Ok(Some((card, event)))
+            }
+            Err(e) => {
+                eprintln!("Failed to parse service card: {e}");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Fetch every service card of an author, newest version of each, by card id.
+    pub async fn get_service_cards(
+        &self,
+        author: PublicKey,
+    ) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        let mut cards = self.get_service_cards_by_authors(&[author]).await?;
+        for (card, event) in &mut cards {
+            self.merge_overflow(card, event.pubkey).await?;
+        }
+        Ok(cards)
+    }
+
+    /// Fetch every service card of several authors in one request, newest
+    /// version of each, by author and card id.
+    ///
+    /// Overflow capabilities of split cards are not merged; use
+    /// [`get_service_card`](Self::get_service_card) for a complete card.
+    pub async fn get_service_cards_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(ServiceCard, Event)>, Error> {
+        if authors.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let filter = Filter::new()
+            .kind(Kind::Custom(KIND_SERVICE_CARD))
+            .authors(authors.iter().copied())
+            .custom_tag(SingleLetterTag::uppercase(Alphabet::L), [LABEL_NAMESPACE])
+            .limit(MAX_CARDS_PER_AUTHOR * authors.len());
+
+        let events = self.fetch(vec![filter]).await?;
 
         let mut cards = Vec::new();
         for event in events {
             match ServiceCard::try_from(&event) {
+                Ok(card) if card.is_expired() => {}
                 Ok(card) => cards.push((card, event)),
                 Err(e) => eprintln!("Failed to parse service card: {e}"),
             }
         }
 
+        // Relays may still hold older versions of the replaceable events
+        cards.sort_by_key(|(_, event)| Reverse(event.created_at));
+        let mut seen = HashSet::new();
+        cards.retain(|(card, event)| seen.insert((event.pubkey, card.id.clone())));
+        cards.sort_by(|a, b| (a.1.pubkey, &a.0.id).cmp(&(b.1.pubkey, &b.0.id)));
         Ok(cards)
     }
 
+    /// Fetch the overflow events of a split card, newest version of each first.
+    pub async fn get_overflow_parts(
+        &self,
+        author: PublicKey,
+        card_id: &str,
+    ) -> Result<Vec<OverflowPart>, Error> {
+        let root = Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), author).identifier(card_id);
+        let filter = Filter::new()
+            .kind(Kind::Custom(KIND_SERVICE_CARD_OVERFLOW))
+            .author(author)
+            .custom_tag(SingleLetterTag::lowercase(Alphabet::A), [root.to_string()]);
+
+        let events = self.fetch(vec![filter]).await?;
+
+        let mut events: Vec<Event> = events.into_iter().collect();
+        events.sort_by_key(|event| Reverse(event.created_at));
+
+        let mut parts = Vec::new();
+        for event in events {
+            match OverflowPart::try_from(&event) {
+                Ok(part) => parts.push(part),
+                Err(e) => eprintln!("Failed to parse overflow event: {e}"),
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Merge a split card's overflow events into it; no-op for unsplit cards.
+    async fn merge_overflow(&self, card: &mut ServiceCard, author: PublicKey) -> Result<(), Error> {
+        if card.overflow.is_some() {
+            let parts = self.get_overflow_parts(author, &card.id).await?;
+            card.merge_overflow(parts);
+        }
+        Ok(())
+    }
+
+    /// Follow successor pointers from a card to the newest card in the chain.
+    ///
+    /// Stops at a card without successor, a successor that can't be found,
+    /// a loop, or after
+    /// [`MAX_SUCCESSOR_HOPS`](crate::deprecation::MAX_SUCCESSOR_HOPS) hops.
+    pub async fn follow_successors(
+        &self,
+        card: ServiceCard,
+        event: Event,
+    ) -> Result<SuccessorChain, Error> {
+        SuccessorChain::follow(card, event, |successor| async move {
+            self.get_service_card(&successor).await
+        })
+        .await
+    }
+
+    /// Bring a local cache up to date with each connected relay.
+    ///
+    /// Only events newer than the last sync from that relay are requested,
+    /// in pages walked back with `until` since relays silently cap how many
+    /// events they return. Returns the number of events stored.
+    #[cfg(feature = "cache")]
+    pub async fn sync_cache(&self, cache: &mut crate::DiscoveryCache) -> Result<usize, Error> {
+        let mut stored = 0;
+        for (url, relay) in self.client.relays().await {
+            let started = Timestamp::now();
+            let mut filter = Filter::new()
+                .kinds([
+                    Kind::Custom(KIND_SERVICE_CARD),
+                    Kind::Custom(KIND_SERVICE_CARD_OVERFLOW),
+                    Kind::Custom(KIND_HEARTBEAT),
+                ])
+                .custom_tag(SingleLetterTag::uppercase(Alphabet::L), [LABEL_NAMESPACE])
+                .limit(SYNC_PAGE_SIZE);
+            if let Some(last) = cache.last_sync(&url)? {
+                let since = last.as_u64().saturating_sub(SYNC_OVERLAP_SECS);
+                filter = filter.since(Timestamp::from(since));
+            }
+
+            // `until` is inclusive, so pages overlap at their edge; stop once
+            // one brings nothing new
+            let mut seen = HashSet::new();
+            loop {
+                let page = self.fetch_from(vec![url.clone()], vec![filter.clone()]).await?;
+                let mut until: Option<Timestamp> = None;
+                for event in page {
+                    if !seen.insert(event.id) {
+                        continue;
+                    }
+                    until = Some(until.map_or(event.created_at, |t| t.min(event.created_at)));
+                    if cache.insert(&event)? {
+                        stored += 1;
+                    }
+                }
+                match until {
+                    Some(until) => filter = filter.until(until),
+                    None => break,
+                }
+            }
+
+            // An unreachable relay returns no events; don't record that as a sync
+            if relay.is_connected() {
+                cache.set_last_sync(&url, started)?;
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Get heartbeats for all cards of several agents in one request.
+    pub async fn get_heartbeats_by_authors(
+        &self,
+        authors: &[PublicKey],
+    ) -> Result<Vec<(Heartbeat, Event)>, Error> {
+        // An empty author list would match every heartbeat on the relay
+        if authors.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let filter = Filter::new()
+            .kind(Kind::Custom(KIND_HEARTBEAT))
+            .authors(authors.iter().copied())
+            .custom_tag(SingleLetterTag::uppercase(Alphabet::L), [LABEL_NAMESPACE]);
+
+        let events = self.fetch(vec![filter]).await?;
+
+        let mut heartbeats = Vec::new();
+        for event in events {
+            match Heartbeat::try_from(&event) {
+                Ok(hb) if hb.is_expired() => {}
+                Ok(hb) => heartbeats.push((hb, event)),
+                Err(e) => eprintln!("Failed to parse heartbeat: {e}"),
+            }
+        }
+
+        Ok(heartbeats)
+    }
+
     /// Get heartbeats for a service card.
     pub async fn get_heartbeats(
         &self,
@@ -118,15 +864,12 @@ impl AgentDiscoveryClient {
             .custom_tag(SingleLetterTag::lowercase(Alphabet::D), [service_card_id])
             .limit(limit);
 
-        let events = self
-            .client
-            .fetch_events(vec![filter], Some(Duration::from_secs(10)))
-            .await
-            .map_err(|e| Error::RelayError(e.to_string()))?;
+        let events = self.fetch(vec![filter]).await?;
 
         let mut heartbeats = Vec::new();
         for event in events {
             match Heartbeat::try_from(&event) {
+                Ok(hb) if hb.is_expired() => {}
                 Ok(hb) => heartbeats.push((hb, event)),
                 Err(e) => eprintln!("Failed to parse heartbeat: {e}"),
             }
@@ -135,3 +878,219 @@ impl AgentDiscoveryClient {
         Ok(heartbeats)
     }
 }
+
+impl Drop for AgentDiscoveryClient {
+    fn drop(&mut self) {
+        self.stop_listening();
+    }
+}
+
+/// Limits from a relay's NIP-11 `limitation`.
+fn limits_of(limitation: &Limitation) -> RelayLimits {
+    let limit = |value: Option<i32>| value.and_then(|v| usize::try_from(v).ok());
+    RelayLimits {
+        max_message_length: limit(limitation.max_message_length),
+        max_event_tags: limit(limitation.max_event_tags),
+        max_content_length: limit(limitation.max_content_length),
+    }
+}
+
+/// Why a relay would refuse a signed event, judging by its NIP-11 `limitation`.
+fn rejection(limitation: &Limitation, event: &Event, authenticated: bool) -> Option<String> {
+    if limitation.auth_required == Some(true) && !authenticated {
+        return Some("relay requires authentication".to_string());
+    }
+    if limitation.payment_required == Some(true) {
+        return Some("relay requires payment".to_string());
+    }
+    if let Some(difficulty) = limitation.min_pow_difficulty.filter(|d| *d > 0) {
+        let difficulty = u8::try_from(difficulty).unwrap_or(u8::MAX);
+        if !event.id.check_pow(difficulty) {
+            return Some(format!("relay requires proof of work of {difficulty} bits"));
+        }
+    }
+    limits_of(limitation)
+        .check(&EventSize::of(event))
+        .first()
+        .map(|warning| warning.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+
+    const DOCUMENT: &str = r#"{
+        "name": "Test relay",
+        "limitation": {
+            "max_message_length": 4096,
+            "max_event_tags": 10,
+            "max_content_length": 200,
+            "auth_required": false
+        }
+    }"#;
+
+    /// Serve `document` as a NIP-11 document on a loopback port, counting
+    /// requests for it. Websocket upgrades are refused.
+    fn serve_nip11(document: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&requests);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+
+                let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                let response = if request.contains("accept: application/nostr+json") {
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/nostr+json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{document}",
+                        document.len()
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn event_with_tags(tags: usize, content: &str) -> Event {
+        let tags = (0..tags).map(|i| Tag::parse(["t", &format!("tag{i}")]).unwrap());
+        EventBuilder::new(Kind::TextNote, content)
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn client_builds_without_a_runtime() {
+        let mut new = std::pin::pin!(AgentDiscoveryClient::new(Keys::generate()));
+        let mut cx = Context::from_waker(Waker::noop());
+        let Poll::Ready(client) = new.as_mut().poll(&mut cx) else {
+            panic!("creating a client waited on a runtime");
+        };
+        let client = client.unwrap();
+        assert!(client.challenge_listener.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn relay_info_is_fetched_on_connect_and_cached() {
+        let (url, requests) = serve_nip11(DOCUMENT);
+        let client = AgentDiscoveryClient::new(Keys::generate()).await.unwrap();
+        client.connect(&[&url]).await.unwrap();
+
+        let info = client.relay_info();
+        assert_eq!(info.len(), 1);
+        let doc = info.values().next().unwrap();
+        assert_eq!(doc.name.as_deref(), Some("Test relay"));
+        assert_eq!(
+            client.relay_limits(),
+            Some(RelayLimits {
+                max_message_length: Some(4096),
+                max_event_tags: Some(10),
+                max_content_length: Some(200),
+            })
+        );
+
+        // Already cached: not requested again
+        client.fetch_relay_info().await;
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn relays_without_a_document_have_no_limits() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = AgentDiscoveryClient::new(Keys::generate()).await.unwrap();
+        client.connect(&[&url]).await.unwrap();
+        assert!(client.relay_info().is_empty());
+        assert_eq!(client.relay_limits(), None);
+        client.disconnect().await.unwrap();
+    }
+
+    #[test]
+    fn rejection_skips_relays_requiring_auth_unless_authenticated() {
+        let limitation = Limitation {
+            auth_required: Some(true),
+            ..Default::default()
+        };
+        let event = event_with_tags(1, "hi");
+        assert!(rejection(&limitation, &event, false).unwrap().contains("authentication"));
+        assert_eq!(rejection(&limitation, &event, true), None);
+    }
+
+    #[test]
+    fn rejection_skips_paid_relays() {
+        let limitation = Limitation {
+            payment_required: Some(true),
+            ..Default::default()
+        };
+        let reason = rejection(&limitation, &event_with_tags(1, "hi"), true).unwrap();
+        assert!(reason.contains("payment"));
+    }
+
+    #[test]
+    fn rejection_checks_proof_of_work() {
+        let limitation = Limitation {
+            min_pow_difficulty: Some(8),
+            ..Default::default()
+        };
+        let keys = Keys::generate();
+        let mined = EventBuilder::text_note("mined")
+            .pow(8)
+            .sign_with_keys(&keys)
+            .unwrap();
+        let unmined = (0..)
+            .map(|i| EventBuilder::text_note(format!("plain {i}")).sign_with_keys(&keys).unwrap())
+            .find(|event| !event.id.check_pow(8))
+            .unwrap();
+
+        assert_eq!(rejection(&limitation, &mined, false), None);
+        assert!(rejection(&limitation, &unmined, false).unwrap().contains("proof of work"));
+    }
+
+    #[test]
+    fn rejection_checks_size_limits() {
+        let limitation = Limitation {
+            max_event_tags: Some(3),
+            max_content_length: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(rejection(&limitation, &event_with_tags(3, "short"), false), None);
+        let reason = rejection(&limitation, &event_with_tags(4, "short"), false).unwrap();
+        assert!(reason.contains("4 tags"));
+        let reason = rejection(&limitation, &event_with_tags(0, "far too long"), false).unwrap();
+        assert!(reason.contains("content"));
+    }
+
+    #[test]
+    fn rejection_accepts_unrestricted_relays() {
+        let event = event_with_tags(200, &"x".repeat(100_000));
+        assert_eq!(rejection(&Limitation::default(), &event, false), None);
+    }
+}
@@ -0,0 +1,357 @@
+//! Scheduled availability
+//!
+//! Recurring weekly windows when an agent expects to be up, and one-off
+//! planned maintenance. Times use a fixed UTC offset rather than a named
+//! timezone, so agents in zones with daylight saving republish on change.
+
+use nostr::{Tag, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+const SECS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    /// Weekday of a day count since the unix epoch (1970-01-01 was a Thursday).
+    fn from_epoch_day(day: i64) -> Self {
+        Self::ALL[(day + 3).rem_euclid(7) as usize]
+    }
+}
+
+impl std::fmt::Display for Weekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Weekday::Mon => write!(f, "mon"),
+            Weekday::Tue => write!(f, "tue"),
+            Weekday::Wed => write!(f, "wed"),
+            Weekday::Thu => write!(f, "thu"),
+            Weekday::Fri => write!(f, "fri"),
+            Weekday::Sat => write!(f, "sat"),
+            Weekday::Sun => write!(f, "sun"),
+        }
+    }
+}
+
+impl std::str::FromStr for Weekday {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mon" => Ok(Weekday::Mon),
+            "tue" => Ok(Weekday::Tue),
+            "wed" => Ok(Weekday::Wed),
+            "thu" => Ok(Weekday::Thu),
+            "fri" => Ok(Weekday::Fri),
+            "sat" => Ok(Weekday::Sat),
+            "sun" => Ok(Weekday::Sun),
+            other => Err(Error::ParseError(format!("unknown weekday: {other}"))),
+        }
+    }
+}
+
+/// A recurring weekly window, e.g. Mon–Fri 09:00–17:00 at UTC+01:00.
+///
+/// Encoded as `["hours", "mon-fri", "09:00-17:00", "+01:00"]`. A window whose
+/// end is before its start runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AvailabilityWindow {
+    pub days: Vec<Weekday>,
+    /// Minutes after local midnight
+    pub start: u16,
+    /// Minutes after local midnight
+    pub end: u16,
+    /// Offset from UTC in minutes
+    pub utc_offset: i16,
+}
+
+impl AvailabilityWindow {
+    /// Whether `at` falls inside the window.
+    pub fn contains(&self, at: Timestamp) -> bool {
+        let local = at.as_u64() as i64 + i64::from(self.utc_offset) * 60;
+        let day = local.div_euclid(SECS_PER_DAY);
+        let minute = (local.rem_euclid(SECS_PER_DAY) / 60) as u16;
+
+        let today = self.days.contains(&Weekday::from_epoch_day(day));
+        if self.start < self.end {
+            return today && minute >= self.start && minute < self.end;
+        }
+
+        // Overnight window: started today, or started yesterday and still running
+        let yesterday = self.days.contains(&Weekday::from_epoch_day(day - 1));
+        (today && minute >= self.start) || (yesterday && minute < self.end)
+    }
+
+    /// Start of the next occurrence strictly after `after`.
+    pub fn next_start(&self, after: Timestamp) -> Option<Timestamp> {
+        let offset = i64::from(self.utc_offset) * 60;
+        let local = after.as_u64() as i64 + offset;
+        let today = local.div_euclid(SECS_PER_DAY);
+
+        (0..=7)
+            .map(|d| today + d)
+            .filter(|day| self.days.contains(&Weekday::from_epoch_day(*day)))
+            .map(|day| day * SECS_PER_DAY + i64::from(self.start) * 60 - offset)
+            .find(|start| *start > after.as_u64() as i64)
+            .map(|start| Timestamp::from(start as u64))
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        Tag::parse([
+            "hours".to_string(),
+            format_days(&self.days),
+            format!("{}-{}", format_minutes(self.start), format_minutes(self.end)),
+            format_offset(self.utc_offset),
+        ])
+        .unwrap()
+    }
+
+    /// Parse from the tag values after `"hours"`: days, time range and optional offset.
+    pub fn parse(days: &str, range: &str, offset: Option<&str>) -> Result<Self, Error> {
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| Error::ParseError(format!("invalid time range: {range}")))?;
+
+        Ok(Self {
+            days: parse_days(days)?,
+            start: parse_minutes(start)?,
+            end: parse_minutes(end)?,
+            utc_offset: offset.map(parse_offset).transpose()?.unwrap_or(0),
+        })
+    }
+}
+
+impl std::fmt::Display for AvailabilityWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = format_days(&self.days);
+        let start = format_minutes(self.start);
+        let end = format_minutes(self.end);
+        let offset = format_offset(self.utc_offset);
+        write!(f, "{days} {start}-{end} UTC{offset}")
+    }
+}
+
+/// A one-off planned maintenance period.
+///
+/// Encoded as `["maintenance", "<start>", "<end>", "<reason>"]` in unix seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl MaintenanceWindow {
+    pub fn contains(&self, at: Timestamp) -> bool {
+        self.start <= at && at < self.end
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        let mut values = vec![
+            "maintenance".to_string(),
+            self.start.to_string(),
+            self.end.to_string(),
+        ];
+        if let Some(reason) = &self.reason {
+            values.push(reason.clone());
+        }
+        Tag::parse(values).unwrap()
+    }
+}
+
+/// Parse `mon-fri`, `sat,sun`, `mon,wed-fri` or `daily`.
+fn parse_days(s: &str) -> Result<Vec<Weekday>, Error> {
+    if s == "daily" {
+        return Ok(Weekday::ALL.to_vec());
+    }
+
+    let mut days = Vec::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let from = from.parse::<Weekday>()? as usize;
+                let to = to.parse::<Weekday>()? as usize;
+                if from > to {
+                    return Err(Error::ParseError(format!("invalid day range: {part}")));
+                }
+                days.extend_from_slice(&Weekday::ALL[from..=to]);
+            }
+            None => days.push(part.parse()?),
+        }
+    }
+    days.sort();
+    days.dedup();
+    Ok(days)
+}
+
+fn format_days(days: &[Weekday]) -> String {
+    if days.len() == 7 {
+        return "daily".to_string();
+    }
+    days.iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse `HH:MM` into minutes after midnight. `24:00` is allowed as an end time.
+fn parse_minutes(s: &str) -> Result<u16, Error> {
+    let invalid = || Error::ParseError(format!("invalid time: {s}"));
+    let (h, m) = s.split_once(':').ok_or_else(invalid)?;
+    let h: u16 = h.parse().map_err(|_| invalid())?;
+    let m: u16 = m.parse().map_err(|_| invalid())?;
+    if h > 24 || m >= 60 || h * 60 + m > 24 * 60 {
+        return Err(invalid());
+    }
+    Ok(h * 60 + m)
+}
+
+fn format_minutes(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Parse `UTC`, `Z`, `+01:00` or `-05:30` into minutes.
+fn parse_offset(s: &str) -> Result<i16, Error> {
+    if s == "UTC" || s == "Z" {
+        return Ok(0);
+    }
+
+    let invalid = || Error::ParseError(format!("invalid UTC offset: {s}"));
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => return Err(invalid()),
+    };
+    let minutes = parse_minutes(rest).map_err(|_| invalid())?;
+    if minutes > 14 * 60 {
+        return Err(invalid());
+    }
+    Ok(sign * minutes as i16)
+}
+
+fn format_offset(offset: i16) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("{sign}{}", format_minutes(offset.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday 2024-01-01 00:00 UTC
+    const MONDAY: u64 = 1_704_067_200;
+
+    /// `day` days after that Monday at `hh:mm` UTC.
+    fn at(day: u64, hh: u64, mm: u64) -> Timestamp {
+        Timestamp::from(MONDAY + day * 86_400 + hh * 3_600 + mm * 60)
+    }
+
+    fn window(days: &str, range: &str, offset: Option<&str>) -> AvailabilityWindow {
+        AvailabilityWindow::parse(days, range, offset).unwrap()
+    }
+
+    #[test]
+    fn weekday_window() {
+        let office = window("mon-fri", "09:00-17:00", None);
+        assert!(office.contains(at(0, 9, 0)));
+        assert!(office.contains(at(4, 16, 59)));
+        assert!(!office.contains(at(0, 8, 59)));
+        assert!(!office.contains(at(0, 17, 0)));
+        assert!(!office.contains(at(5, 10, 0)));
+        assert!(!office.contains(at(6, 10, 0)));
+    }
+
+    #[test]
+    fn window_with_utc_offset() {
+        let office = window("mon", "09:00-17:00", Some("+01:00"));
+        assert!(office.contains(at(0, 8, 30)));
+        assert!(!office.contains(at(0, 16, 30)));
+
+        // Monday 00:00-02:00 at UTC-05:00 is Monday 05:00-07:00 UTC
+        let late = window("mon", "00:00-02:00", Some("-05:00"));
+        assert!(late.contains(at(0, 5, 30)));
+        assert!(!late.contains(at(0, 0, 30)));
+    }
+
+    #[test]
+    fn overnight_window_runs_into_the_next_day() {
+        let night = window("fri", "22:00-06:00", None);
+        assert!(night.contains(at(4, 23, 0)));
+        assert!(night.contains(at(5, 5, 59)));
+        assert!(!night.contains(at(5, 6, 0)));
+        assert!(!night.contains(at(4, 5, 0)));
+        assert!(!night.contains(at(4, 21, 59)));
+    }
+
+    #[test]
+    fn next_start_skips_to_the_next_listed_day() {
+        let office = window("mon-fri", "09:00-17:00", None);
+        assert_eq!(office.next_start(at(0, 8, 0)), Some(at(0, 9, 0)));
+        assert_eq!(office.next_start(at(0, 9, 0)), Some(at(1, 9, 0)));
+        assert_eq!(office.next_start(at(4, 18, 0)), Some(at(7, 9, 0)));
+    }
+
+    #[test]
+    fn days_parse_lists_and_ranges() {
+        use Weekday::*;
+        assert_eq!(parse_days("mon,wed-fri").unwrap(), [Mon, Wed, Thu, Fri]);
+        assert_eq!(parse_days("sun,sat,sat").unwrap(), [Sat, Sun]);
+        assert_eq!(parse_days("daily").unwrap(), Weekday::ALL);
+        assert!(parse_days("fri-mon").is_err());
+        assert!(parse_days("someday").is_err());
+    }
+
+    #[test]
+    fn times_and_offsets_are_validated() {
+        assert_eq!(parse_minutes("24:00").unwrap(), 1_440);
+        assert!(parse_minutes("24:01").is_err());
+        assert!(parse_minutes("9:60").is_err());
+        assert_eq!(parse_offset("-05:30").unwrap(), -330);
+        assert_eq!(parse_offset("Z").unwrap(), 0);
+        assert!(parse_offset("+15:00").is_err());
+        assert!(parse_offset("01:00").is_err());
+    }
+
+    #[test]
+    fn window_round_trips_through_its_tag() {
+        let original = window("mon,wed-fri", "22:30-06:00", Some("-05:30"));
+        let tag = original.to_tag();
+        let values = tag.as_slice();
+        assert_eq!(values, ["hours", "mon,wed,thu,fri", "22:30-06:00", "-05:30"]);
+        assert_eq!(window(&values[1], &values[2], Some(&values[3])), original);
+    }
+
+    #[test]
+    fn maintenance_window_is_half_open() {
+        let maintenance = MaintenanceWindow {
+            start: at(2, 1, 0),
+            end: at(2, 3, 0),
+            reason: None,
+        };
+        assert!(maintenance.contains(at(2, 1, 0)));
+        assert!(!maintenance.contains(at(2, 3, 0)));
+        assert!(!maintenance.contains(at(2, 0, 59)));
+    }
+}
@@ -1,16 +1,59 @@
 //! Agent Service Card
 
+use std::borrow::Cow;
+
 use nostr::event::{Event, EventBuilder, Kind};
 use nostr::key::Keys;
-use nostr::Tag;
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip19::ToBech32;
+use nostr::{PublicKey, RelayUrl, Tag, Timestamp};
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, KIND_SERVICE_CARD, LABEL_NAMESPACE, LABEL_SERVICE_CARD};
+use crate::budget::{BudgetWarning, EventSize, RelayLimits};
+use crate::deprecation::{Deprecation, SUCCESSOR_MARKER};
+use crate::heartbeat::parse_timestamp;
+use crate::overflow::OverflowPart;
+use crate::policy::Policy;
+use crate::pricing::{PaymentMethod, Pricing};
+use crate::schedule::{AvailabilityWindow, MaintenanceWindow};
+use crate::{
+    vocabulary, Error, KIND_SERVICE_CARD, LABEL_LANGUAGE_NAMESPACE, LABEL_NAMESPACE,
+    LABEL_SERVICE_CARD, MAX_CONTENT_BYTES,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A capability the agent offers.
+///
+/// Optional metadata is carried as `key=value` elements after the description
+/// in the `c` tag, e.g. `["c", "transcription", "...", "in=audio/wav", "out=text/plain"]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Capability {
     pub id: String,
     pub description: String,
+
+    /// Accepted input MIME types or bare modalities (e.g. `audio/wav`, `image`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input: Vec<String>,
+
+    /// Produced output MIME types or bare modalities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output: Vec<String>,
+
+    /// JSON Schema URL describing the job parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+
+    /// Maximum accepted input size in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_bytes: Option<u64>,
+
+    /// Contract version, carried as `v=<semver>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+
+    /// Price for this capability, overriding the card's pricing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
 }
 
 impl Capability {
@@ -18,12 +61,226 @@ impl Capability {
         Self {
             id: id.into(),
             description: description.into(),
+            ..Default::default()
         }
     }
 
+    pub fn input(mut self, mime: impl Into<String>) -> Self {
+        self.input.push(mime.into());
+        self
+    }
+
+    pub fn output(mut self, mime: impl Into<String>) -> Self {
+        self.output.push(mime.into());
+        self
+    }
+
+    pub fn schema(mut self, url: impl Into<String>) -> Self {
+        self.schema = Some(url.into());
+        self
+    }
+
+    pub fn max_input_bytes(mut self, bytes: u64) -> Self {
+        self.max_input_bytes = Some(bytes);
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Ancestor ids in the dotted taxonomy, nearest root first.
+    ///
+    /// `code.review.rust` yields `code` and `code.review`.
+    pub fn ancestors(&self) -> impl Iterator<Item = &str> {
+        let id = self.id.as_str();
+        id.match_indices('.').map(move |(i, _)| &id[..i])
+    }
+
+    /// Whether this capability is `query` or one of its descendants.
+    pub fn matches(&self, query: &str) -> bool {
+        capability_matches(&self.id, query)
+    }
+
+    /// Whether this capability accepts the given MIME type or modality.
+    pub fn accepts(&self, mime: &str) -> bool {
+        self.input.iter().any(|declared| mime_matches(declared, mime))
+    }
+
+    /// Whether this capability produces the given MIME type or modality.
+    pub fn produces(&self, mime: &str) -> bool {
+        self.output.iter().any(|declared| mime_matches(declared, mime))
+    }
+
     pub fn to_tag(&self) -> Tag {
-        Tag::parse(["c", &self.id, &self.description]).unwrap()
+        let mut values = vec![
+            "c".to_string(),
+            self.id.clone(),
+            self.description.clone(),
+        ];
+        values.extend(self.input.iter().map(|m| format!("in={m}")));
+        values.extend(self.output.iter().map(|m| format!("out={m}")));
+        if let Some(schema) = &self.schema {
+            values.push(format!("schema={schema}"));
+        }
+        if let Some(max) = self.max_input_bytes {
+            values.push(format!("max={max}"));
+        }
+        if let Some(version) = &self.version {
+            values.push(format!("v={version}"));
+        }
+        if let Some(pricing) = &self.pricing {
+            values.push(format!("price={pricing}"));
+        }
+        Tag::parse(values).unwrap()
+    }
+
+    /// Parse a `c` tag's values (including the leading `"c"`).
+    ///
+    /// Unknown metadata keys and malformed values are ignored so newer
+    /// publishers stay readable.
+    pub(crate) fn from_tag_values(values: &[&str]) -> Self {
+        let mut cap = Capability::new(values[1], values[2]);
+
+        for element in &values[3..] {
+            let Some((key, value)) = element.split_once('=') else {
+                continue;
+            };
+            match key {
+                "in" => cap.input.push(value.to_string()),
+                "out" => cap.output.push(value.to_string()),
+                "schema" => cap.schema = Some(value.to_string()),
+                "max" => cap.max_input_bytes = value.parse().ok(),
+                "v" => cap.version = Version::parse(value).ok(),
+                "price" => cap.pricing = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        cap
+    }
+}
+
+/// Displays as `id` or `id@version`.
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{version}", self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// A capability query with an optional semver requirement, e.g. `translation@^2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityRequirement {
+    pub id: String,
+    pub version: Option<VersionReq>,
+}
+
+impl CapabilityRequirement {
+    /// Whether the card offers a matching capability at a compatible version.
+    ///
+    /// Ids match hierarchically and through well-known aliases. When a version
+    /// is required, capabilities without a declared version never match.
+    pub fn is_satisfied_by(&self, card: &ServiceCard) -> bool {
+        let ids = vocabulary::expand(&self.id);
+        card.capabilities.iter().any(|cap| {
+            ids.iter().any(|id| cap.matches(id))
+                && match (&self.version, &cap.version) {
+                    (None, _) => true,
+                    (Some(req), Some(version)) => req.matches(version),
+                    (Some(_), None) => false,
+                }
+        })
+    }
+}
+
+impl std::str::FromStr for CapabilityRequirement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, version) = match s.split_once('@') {
+            Some((id, req)) => {
+                let req = VersionReq::parse(req).map_err(|e| {
+                    Error::InvalidCapability(format!("invalid version requirement '{req}': {e}"))
+                })?;
+                (id, Some(req))
+            }
+            None => (s, None),
+        };
+
+        if id.is_empty() {
+            return Err(Error::InvalidCapability(format!("missing capability id in '{s}'")));
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            version,
+        })
+    }
+}
+
+/// Whether capability `id` equals `query` or sits below it in the dotted taxonomy.
+///
+/// `audio` matches `audio` and `audio.transcription`, but not `audiobook`.
+pub fn capability_matches(id: &str, query: &str) -> bool {
+    id.strip_prefix(query)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Ancestor ids as bare `c` tags so relays can filter on a prefix (e.g. `#c: ["audio"]`).
+pub(crate) fn ancestor_tags(capabilities: &[Capability]) -> Vec<Tag> {
+    let mut ancestors: Vec<&str> = Vec::new();
+    for ancestor in capabilities.iter().flat_map(|cap| cap.ancestors()) {
+        let declared = capabilities.iter().any(|cap| cap.id == ancestor);
+        if !declared && !ancestors.contains(&ancestor) {
+            ancestors.push(ancestor);
+        }
+    }
+    ancestors
+        .into_iter()
+        .map(|ancestor| Tag::parse(["c", ancestor]).unwrap())
+        .collect()
+}
+
+/// Whether `s` looks like a two-letter ISO 639-1 code.
+fn is_language_code(s: &str) -> bool {
+    s.len() == 2 && s.bytes().all(|b| b.is_ascii_lowercase())
+}
+
+/// Primary subtag of a language tag, as carried in `l` labels (`de-CH` is `de`).
+pub(crate) fn primary_language(lang: &str) -> String {
+    lang.split(['-', '_'])
+        .next()
+        .unwrap_or(lang)
+        .to_ascii_lowercase()
+}
+
+/// Match a declared MIME type or modality against a wanted one.
+///
+/// `audio/*` and bare `audio` match any `audio/...` type on either side, so
+/// `audio/*` wanted matches a declared `audio/wav` and vice versa.
+fn mime_matches(declared: &str, wanted: &str) -> bool {
+    if declared.eq_ignore_ascii_case(wanted) {
+        return true;
+    }
+
+    let declared_type = declared.split('/').next().unwrap_or(declared);
+    let wanted_type = wanted.split('/').next().unwrap_or(wanted);
+    if !declared_type.eq_ignore_ascii_case(wanted_type) {
+        return false;
     }
+
+    let declared_sub = declared.split_once('/').map(|(_, sub)| sub);
+    let wanted_sub = wanted.split_once('/').map(|(_, sub)| sub);
+    matches!(declared_sub, None | Some("*")) || matches!(wanted_sub, None | Some("*"))
 }
 
 /// Communication protocol for reaching an agent.
@@ -31,11 +288,14 @@ impl Capability {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Protocol {
-    /// Direct messages (NIP-04/17) — relays where agent listens
-    Dm { relays: String },
+    /// Legacy encrypted direct messages (NIP-04) — relays where agent listens
+    Dm { relays: Vec<RelayUrl> },
+
+    /// Gift-wrapped private direct messages (NIP-17) — relays where agent listens
+    Nip17 { relays: Vec<RelayUrl> },
 
     /// NIP-90 Data Vending Machine — relays + supported job kinds
-    Dvm { relays: String, kinds: Vec<u16> },
+    Dvm { relays: Vec<RelayUrl>, kinds: Vec<u16> },
 
     /// Google A2A protocol — agent card URL
     A2a { url: String },
@@ -46,25 +306,58 @@ pub enum Protocol {
     /// HTTP REST API — API endpoint
     Http { url: String },
 
+    /// HTTP API described by an OpenAPI document — document URL
+    OpenApi { url: String },
+
+    /// Raw WebSocket endpoint
+    WebSocket { url: String },
+
+    /// gRPC service endpoint
+    Grpc { url: String },
+
     /// Custom protocol — for extensibility
     Custom { id: String, endpoint: String },
 }
 
 impl Protocol {
     // Convenience constructors
-    pub fn dm(relays: impl Into<String>) -> Self {
+    pub fn dm(relays: impl IntoIterator<Item = RelayUrl>) -> Self {
         Self::Dm {
-            relays: relays.into(),
+            relays: relays.into_iter().collect(),
         }
     }
 
-    pub fn dvm(relays: impl Into<String>, kinds: Vec<u16>) -> Self {
+    pub fn nip17(relays: impl IntoIterator<Item = RelayUrl>) -> Self {
+        Self::Nip17 {
+            relays: relays.into_iter().collect(),
+        }
+    }
+
+    pub fn dvm(relays: impl IntoIterator<Item = RelayUrl>, kinds: Vec<u16>) -> Self {
         Self::Dvm {
-            relays: relays.into(),
+            relays: relays.into_iter().collect(),
             kinds,
         }
     }
 
+    /// Parse a comma-separated relay list, as carried in the `r` tag endpoint.
+    ///
+    /// An empty string yields an empty list; any malformed entry is rejected.
+    pub fn parse_relays(s: &str) -> Result<Vec<RelayUrl>, Error> {
+        if s.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        s.split(',')
+            .map(|entry| {
+                let entry = entry.trim();
+                RelayUrl::parse(entry).map_err(|e| {
+                    Error::InvalidProtocol(format!("invalid relay URL '{entry}': {e}"))
+                })
+            })
+            .collect()
+    }
+
     pub fn a2a(url: impl Into<String>) -> Self {
         Self::A2a { url: url.into() }
     }
@@ -77,6 +370,18 @@ impl Protocol {
         Self::Http { url: url.into() }
     }
 
+    pub fn openapi(url: impl Into<String>) -> Self {
+        Self::OpenApi { url: url.into() }
+    }
+
+    pub fn websocket(url: impl Into<String>) -> Self {
+        Self::WebSocket { url: url.into() }
+    }
+
+    pub fn grpc(url: impl Into<String>) -> Self {
+        Self::Grpc { url: url.into() }
+    }
+
     pub fn custom(id: impl Into<String>, endpoint: impl Into<String>) -> Self {
         Self::Custom {
             id: id.into(),
@@ -87,27 +392,47 @@ impl Protocol {
     pub fn id(&self) -> &str {
         match self {
             Self::Dm { .. } => "dm",
+            Self::Nip17 { .. } => "nip17",
             Self::Dvm { .. } => "dvm",
             Self::A2a { .. } => "a2a",
             Self::Mcp { .. } => "mcp",
             Self::Http { .. } => "http",
+            Self::OpenApi { .. } => "openapi",
+            Self::WebSocket { .. } => "websocket",
+            Self::Grpc { .. } => "grpc",
             Self::Custom { id, .. } => id,
         }
     }
 
-    pub fn endpoint(&self) -> &str {
+    /// Endpoint as carried in the `r` tag. Relay lists are comma-separated.
+    pub fn endpoint(&self) -> Cow<'_, str> {
+        match self {
+            Self::Dm { relays } => Cow::Owned(join_relays(relays)),
+            Self::Nip17 { relays } => Cow::Owned(join_relays(relays)),
+            Self::Dvm { relays, .. } => Cow::Owned(join_relays(relays)),
+            Self::A2a { url } => Cow::Borrowed(url),
+            Self::Mcp { url } => Cow::Borrowed(url),
+            Self::Http { url } => Cow::Borrowed(url),
+            Self::OpenApi { url } => Cow::Borrowed(url),
+            Self::WebSocket { url } => Cow::Borrowed(url),
+            Self::Grpc { url } => Cow::Borrowed(url),
+            Self::Custom { endpoint, .. } => Cow::Borrowed(endpoint),
+        }
+    }
+
+    /// Relays the agent listens on, for relay-based protocols.
+    pub fn relays(&self) -> Option<&[RelayUrl]> {
         match self {
-            Self::Dm { relays } => relays,
-            Self::Dvm { relays, .. } => relays,
-            Self::A2a { url } => url,
-            Self::Mcp { url } => url,
-            Self::Http { url } => url,
-            Self::Custom { endpoint, .. } => endpoint,
+            Self::Dm { relays } | Self::Nip17 { relays } | Self::Dvm { relays, .. } => {
+                Some(relays)
+            }
+            _ => None,
         }
     }
 
     pub fn to_tags(&self) -> Vec<Tag> {
-        let mut tags = vec![Tag::parse(["r", self.id(), self.endpoint()]).unwrap()];
+        let endpoint = self.endpoint();
+        let mut tags = vec![Tag::parse(["r", self.id(), &endpoint]).unwrap()];
 
         // DVM adds k tags for supported job kinds
         if let Self::Dvm { kinds, .. } = self {
@@ -120,6 +445,45 @@ impl Protocol {
     }
 }
 
+fn join_relays(relays: &[RelayUrl]) -> String {
+    relays
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Non-fatal problem found by [`ServiceCard::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Capability id is neither well-known nor below a well-known id
+    UnknownCapability {
+        id: String,
+        suggestion: Option<&'static str>,
+    },
+
+    /// Capability id is an alias of a well-known id
+    CapabilityAlias { id: String, canonical: &'static str },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::UnknownCapability {
+                id,
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown capability '{id}' (did you mean '{suggestion}'?)"),
+            Warning::UnknownCapability {
+                id,
+                suggestion: None,
+            } => write!(f, "unknown capability '{id}'"),
+            Warning::CapabilityAlias { id, canonical } => {
+                write!(f, "capability '{id}' is an alias, prefer '{canonical}'")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ServiceCard {
     pub id: String,
@@ -127,6 +491,57 @@ pub struct ServiceCard {
     pub about: String,
     pub capabilities: Vec<Capability>,
     pub protocols: Vec<Protocol>,
+
+    /// Long-form Markdown documentation, carried in the event content
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+
+    /// Default price for all capabilities
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub payment_methods: Vec<PaymentMethod>,
+
+    /// NIP-40 expiration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<Timestamp>,
+
+    /// Weekly hours the agent expects to be up; empty means always
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<AvailabilityWindow>,
+
+    /// Planned maintenance periods
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintenance: Vec<MaintenanceWindow>,
+
+    /// Underlying model family, e.g. `llama-3.1` or `claude`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Modalities the agent handles overall (`text`, `image`, `audio`, `video`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modalities: Vec<String>,
+
+    /// Maximum context size in tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+
+    /// Human languages the agent works in, as ISO 639-1 codes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+
+    /// Terms, privacy and data-handling declarations
+    #[serde(default, skip_serializing_if = "Policy::is_empty")]
+    pub policy: Policy,
+
+    /// Set when the card is retired, optionally pointing at its replacement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
+
+    /// Number of overflow events holding further capabilities, until they are merged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overflow: Option<u32>,
 }
 
 impl ServiceCard {
@@ -134,6 +549,285 @@ impl ServiceCard {
         ServiceCardBuilder::new(id, name)
     }
 
+    /// Check the card before publishing.
+    ///
+    /// Malformed ids are errors; capability ids outside the well-known
+    /// vocabulary are reported as warnings.
+    pub fn validate(&self) -> Result<Vec<Warning>, Error> {
+        if self.id.is_empty() {
+            return Err(Error::ParseError("service card id is empty".to_string()));
+        }
+        if self.name.is_empty() {
+            return Err(Error::ParseError("service card name is empty".to_string()));
+        }
+        if self.content.len() > MAX_CONTENT_BYTES {
+            return Err(Error::ParseError(format!(
+                "service card content is {} bytes, limit is {MAX_CONTENT_BYTES}",
+                self.content.len()
+            )));
+        }
+
+        if let Some(lang) = self.languages.iter().find(|lang| !is_language_code(lang)) {
+            return Err(Error::ParseError(format!("'{lang}' is not an ISO 639-1 language code")));
+        }
+
+        self.policy.validate()?;
+        if let Some(deprecation) = &self.deprecation {
+            deprecation.validate()?;
+        }
+
+        let mut warnings = Vec::new();
+        for cap in &self.capabilities {
+            if cap.id.split('.').any(str::is_empty) {
+                return Err(Error::InvalidCapability(format!(
+                    "'{}' has an empty namespace segment",
+                    cap.id
+                )));
+            }
+
+            match vocabulary::lookup(&cap.id) {
+                Some(known) if known.id != cap.id => warnings.push(Warning::CapabilityAlias {
+                    id: cap.id.clone(),
+                    canonical: known.id,
+                }),
+                Some(_) => {}
+                None if vocabulary::is_known(&cap.id) => {}
+                None => warnings.push(Warning::UnknownCapability {
+                    id: cap.id.clone(),
+                    suggestion: vocabulary::suggest(&cap.id).map(|known| known.id),
+                }),
+            }
+        }
+
+        Ok(warnings)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expiration.is_some_and(|exp| exp <= Timestamp::now())
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecation.is_some()
+    }
+
+    /// Address of this card when published by `author` (`31990:<pubkey>:<d>`).
+    pub fn coordinate(&self, author: PublicKey) -> Coordinate {
+        Coordinate::new(Kind::Custom(KIND_SERVICE_CARD), author).identifier(&self.id)
+    }
+
+    /// NIP-19 `naddr` of this card when published by `author`, with relay
+    /// hints where it can be found.
+    pub fn to_naddr(&self, author: PublicKey, relays: &[&str]) -> Result<String, Error> {
+        let mut coordinate = self.coordinate(author);
+        coordinate.relays = relays
+            .iter()
+            .map(|relay| {
+                RelayUrl::parse(relay)
+                    .map_err(|e| Error::ParseError(format!("invalid relay hint {relay}: {e}")))
+            })
+            .collect::<Result<_, _>>()?;
+        coordinate
+            .to_bech32()
+            .map_err(|e| Error::NostrError(format!("failed to encode naddr: {e}")))
+    }
+
+    /// Coordinate of the card replacing this one, if any.
+    pub fn successor(&self) -> Option<&Coordinate> {
+        self.deprecation.as_ref()?.successor.as_ref()
+    }
+
+    /// Effective price of a capability: its own pricing, else the card's.
+    pub fn price_for(&self, capability: &Capability) -> Option<Pricing> {
+        capability.pricing.or(self.pricing)
+    }
+
+    /// Whether the card offers something known to cost at most `max_msats`.
+    ///
+    /// With `capability`, only matching capabilities (including aliases) are
+    /// considered. Cards that declare no price never match.
+    pub fn is_affordable(&self, max_msats: u64, capability: Option<&str>) -> bool {
+        if capability.is_none() && self.capabilities.is_empty() {
+            return self.pricing.is_some_and(|p| p.is_within(max_msats));
+        }
+
+        let ids = capability.map(vocabulary::expand);
+        self.capabilities
+            .iter()
+            .filter(|cap| {
+                ids.as_ref()
+                    .is_none_or(|ids| ids.iter().any(|id| cap.matches(id)))
+            })
+            .filter_map(|cap| self.price_for(cap))
+            .any(|p| p.is_within(max_msats))
+    }
+
+    /// Whether the card offers `query` or any capability below it.
+    pub fn has_capability(&self, query: &str) -> bool {
+        self.capabilities.iter().any(|cap| cap.matches(query))
+    }
+
+    /// Whether any capability accepts `input` and produces `output`.
+    ///
+    /// Either side may be `None` to leave it unconstrained.
+    pub fn supports_io(&self, input: Option<&str>, output: Option<&str>) -> bool {
+        self.capabilities.iter().any(|cap| {
+            input.is_none_or(|mime| cap.accepts(mime))
+                && output.is_none_or(|mime| cap.produces(mime))
+        })
+    }
+
+    /// End of the planned maintenance window covering `now`, if any.
+    pub fn maintenance_end(&self, now: Timestamp) -> Option<Timestamp> {
+        self.maintenance
+            .iter()
+            .filter(|window| window.contains(now))
+            .map(|window| window.end)
+            .max()
+    }
+
+    /// Whether `now` falls within the card's scheduled hours.
+    ///
+    /// Cards without a schedule are always scheduled.
+    pub fn is_scheduled_at(&self, now: Timestamp) -> bool {
+        self.schedule.is_empty() || self.schedule.iter().any(|window| window.contains(now))
+    }
+
+    /// Start of the next scheduled window after `now`.
+    pub fn next_scheduled(&self, now: Timestamp) -> Option<Timestamp> {
+        self.schedule
+            .iter()
+            .filter_map(|window| window.next_start(now))
+            .min()
+    }
+
+    /// Whether the agent works in `lang`.
+    ///
+    /// Only the primary subtag is compared, so `de-AT` matches a card declaring `de`.
+    pub fn speaks(&self, lang: &str) -> bool {
+        let primary = primary_language(lang);
+        self.languages
+            .iter()
+            .any(|declared| declared.eq_ignore_ascii_case(&primary))
+    }
+
+    /// Whether the agent handles `modality`, either declared on the card or
+    /// accepted or produced by one of its capabilities.
+    pub fn supports_modality(&self, modality: &str) -> bool {
+        self.modalities
+            .iter()
+            .any(|declared| declared.eq_ignore_ascii_case(modality))
+            || self
+                .capabilities
+                .iter()
+                .any(|cap| cap.accepts(modality) || cap.produces(modality))
+    }
+
+    /// Estimated size of the card event on the wire.
+    pub fn size_estimate(&self) -> EventSize {
+        EventSize::estimate(&self.to_tags(), &self.content)
+    }
+
+    /// Relay limits the card event would exceed.
+    pub fn check_budget(&self, limits: &RelayLimits) -> Vec<BudgetWarning> {
+        limits.check(&self.size_estimate())
+    }
+
+    /// Split the card so every event fits within `limits`.
+    ///
+    /// Capabilities that don't fit stay in order but move, last first, into
+    /// overflow parts. A card that already fits is returned unchanged with no
+    /// parts. Fails if the card doesn't fit even without capabilities, or a
+    /// single capability doesn't fit into an overflow event of its own.
+    pub fn split(&self, limits: &RelayLimits) -> Result<(ServiceCard, Vec<OverflowPart>), Error> {
+        let mut root = self.clone();
+        root.overflow = None;
+        if limits.fits(&root.size_estimate()) {
+            return Ok((root, Vec::new()));
+        }
+
+        // Reserve room for the overflow tag while trimming the root
+        root.overflow = Some(1);
+        let mut moved = Vec::new();
+        while !limits.fits(&root.size_estimate()) {
+            let Some(cap) = root.capabilities.pop() else {
+                return Err(Error::EventTooLarge(format!(
+                    "service card '{}' exceeds relay limits even without capabilities",
+                    self.id
+                )));
+            };
+            moved.push(cap);
+        }
+        moved.reverse();
+
+        let mut parts = Vec::new();
+        let mut current = OverflowPart::new(&self.id, 0);
+        for cap in moved {
+            current.capabilities.push(cap);
+            if limits.fits(&current.size_estimate()) {
+                continue;
+            }
+            if current.capabilities.len() > 1 {
+                // Start a new part with the capability that didn't fit
+                let cap = current.capabilities.pop().expect("just pushed");
+                let next = OverflowPart::new(&self.id, current.index + 1);
+                parts.push(std::mem::replace(&mut current, next));
+                current.capabilities.push(cap);
+            }
+            if !limits.fits(&current.size_estimate()) {
+                return Err(Error::EventTooLarge(format!(
+                    "capability '{}' alone exceeds relay limits",
+                    current.capabilities[0].id
+                )));
+            }
+        }
+        parts.push(current);
+
+        root.overflow = Some(parts.len() as u32);
+        Ok((root, parts))
+    }
+
+    /// Merge overflow parts back into the card, in order.
+    ///
+    /// Parts for other cards or beyond the announced count (left over from
+    /// an earlier, larger card) are ignored; when the same index appears more
+    /// than once the first one wins. `overflow` is cleared once every part is
+    /// present.
+    pub fn merge_overflow(&mut self, parts: impl IntoIterator<Item = OverflowPart>) {
+        let Some(count) = self.overflow else {
+            return;
+        };
+
+        let mut parts: Vec<OverflowPart> = parts
+            .into_iter()
+            .filter(|part| part.card_id == self.id && part.index < count)
+            .collect();
+        parts.sort_by_key(|part| part.index);
+        parts.dedup_by_key(|part| part.index);
+
+        let complete = parts.len() == count as usize;
+        for part in parts {
+            self.capabilities.extend(part.capabilities);
+        }
+        if complete {
+            self.overflow = None;
+        }
+    }
+
+    /// Relays advertised for direct messages, empty if the card has no DM protocol.
+    ///
+    /// NIP-17 relays are preferred over legacy NIP-04 ones.
+    pub fn dm_relays(&self) -> &[RelayUrl] {
+        let nip17 = self.protocols.iter().find_map(|p| match p {
+            Protocol::Nip17 { relays } => Some(relays.as_slice()),
+            _ => None,
+        });
+        let nip04 = self.protocols.iter().find_map(|p| match p {
+            Protocol::Dm { relays } => Some(relays.as_slice()),
+            _ => None,
+        });
+        nip17.or(nip04).unwrap_or_default()
+    }
+
     pub fn to_tags(&self) -> Vec<Tag> {
         let mut tags = vec![
             // NIP-32 labels
@@ -150,11 +844,60 @@ impl ServiceCard {
             tags.push(cap.to_tag());
         }
 
+        tags.extend(ancestor_tags(&self.capabilities));
+
+        if let Some(parts) = self.overflow {
+            tags.push(Tag::parse(["overflow", &parts.to_string()]).unwrap());
+        }
+
         // Protocols (each may produce multiple tags, e.g., DVM adds k tags)
         for proto in &self.protocols {
             tags.extend(proto.to_tags());
         }
 
+        // Pricing and payment terms
+        if let Some(pricing) = &self.pricing {
+            tags.push(Tag::parse(["price", &pricing.to_string()]).unwrap());
+        }
+        for method in &self.payment_methods {
+            tags.push(method.to_tag());
+        }
+
+        // Runtime metadata; languages are NIP-32 labels so relays can filter on `#l`
+        if let Some(model) = &self.model {
+            tags.push(Tag::parse(["model", model]).unwrap());
+        }
+        for modality in &self.modalities {
+            tags.push(Tag::parse(["modality", modality]).unwrap());
+        }
+        if let Some(context) = self.context_window {
+            tags.push(Tag::parse(["context", &context.to_string()]).unwrap());
+        }
+        if !self.languages.is_empty() {
+            tags.push(Tag::parse(["L", LABEL_LANGUAGE_NAMESPACE]).unwrap());
+        }
+        for lang in &self.languages {
+            tags.push(Tag::parse(["l", lang, LABEL_LANGUAGE_NAMESPACE]).unwrap());
+        }
+
+        tags.extend(self.policy.to_tags());
+
+        // Scheduled availability
+        for window in &self.schedule {
+            tags.push(window.to_tag());
+        }
+        for window in &self.maintenance {
+            tags.push(window.to_tag());
+        }
+
+        if let Some(deprecation) = &self.deprecation {
+            tags.extend(deprecation.to_tags());
+        }
+
+        if let Some(expiration) = self.expiration {
+            tags.push(Tag::parse(["expiration", &expiration.to_string()]).unwrap());
+        }
+
         tags
     }
 
@@ -166,11 +909,25 @@ impl ServiceCard {
             Error::SigningError(format!("failed to sign service card: {err}"))
         })
     }
+
+    /// Sign with NIP-13 proof of work of `difficulty` leading zero bits.
+    ///
+    /// Mining blocks the calling thread; from async code run it on a
+    /// blocking thread, e.g. `tokio::task::spawn_blocking`.
+    pub fn to_event_with_pow(&self, keys: &Keys, difficulty: u8) -> Result<Event, Error> {
+        EventBuilder::from(self)
+            .pow(difficulty)
+            .sign_with_keys(keys)
+            .map_err(|e| {
+                let err = e.to_string();
+                Error::SigningError(format!("failed to sign service card: {err}"))
+            })
+    }
 }
 
 impl From<&ServiceCard> for EventBuilder {
     fn from(card: &ServiceCard) -> Self {
-        EventBuilder::new(Kind::Custom(KIND_SERVICE_CARD), "").tags(card.to_tags())
+        EventBuilder::new(Kind::Custom(KIND_SERVICE_CARD), &card.content).tags(card.to_tags())
     }
 }
 
@@ -184,6 +941,18 @@ impl TryFrom<&Event> for ServiceCard {
         let mut capabilities = Vec::new();
         let mut protocols = Vec::new();
         let mut dvm_kinds = Vec::new();
+        let mut pricing = None;
+        let mut payment_methods = Vec::new();
+        let mut expiration = None;
+        let mut schedule = Vec::new();
+        let mut maintenance = Vec::new();
+        let mut model = None;
+        let mut modalities = Vec::new();
+        let mut context_window = None;
+        let mut languages = Vec::new();
+        let mut policy = Policy::default();
+        let mut deprecation: Option<Deprecation> = None;
+        let mut overflow = None;
 
         for tag in event.tags.iter() {
             let values: Vec<&str> = tag.as_slice().iter().map(|s| s.as_str()).collect();
@@ -191,12 +960,14 @@ impl TryFrom<&Event> for ServiceCard {
                 continue;
             }
 
+            // Skip advisory values this version can't read rather than reject the card
             match values[0] {
                 "d" if values.len() >= 2 => id = Some(values[1].to_string()),
                 "name" if values.len() >= 2 => name = Some(values[1].to_string()),
                 "about" if values.len() >= 2 => about = Some(values[1].to_string()),
+                // Bare `["c", "<ancestor>"]` tags only exist for relay filtering
                 "c" if values.len() >= 3 => {
-                    capabilities.push(Capability::new(values[1], values[2]));
+                    capabilities.push(Capability::from_tag_values(&values));
                 }
                 "r" if values.len() >= 2 => {
                     let proto_id = values[1];
@@ -204,14 +975,22 @@ impl TryFrom<&Event> for ServiceCard {
 
                     // Build protocol - DVM kinds attached later
                     let proto = match proto_id {
-                        "dm" => Protocol::Dm { relays: endpoint },
+                        "dm" => Protocol::Dm {
+                            relays: Protocol::parse_relays(&endpoint)?,
+                        },
+                        "nip17" => Protocol::Nip17 {
+                            relays: Protocol::parse_relays(&endpoint)?,
+                        },
                         "dvm" => Protocol::Dvm {
-                            relays: endpoint,
+                            relays: Protocol::parse_relays(&endpoint)?,
                             kinds: Vec::new(), // filled in below
                         },
                         "a2a" => Protocol::A2a { url: endpoint },
                         "mcp" => Protocol::Mcp { url: endpoint },
                         "http" => Protocol::Http { url: endpoint },
+                        "openapi" => Protocol::OpenApi { url: endpoint },
+                        "websocket" => Protocol::WebSocket { url: endpoint },
+                        "grpc" => Protocol::Grpc { url: endpoint },
                         other => Protocol::Custom {
                             id: other.to_string(),
                             endpoint,
@@ -224,6 +1003,59 @@ impl TryFrom<&Event> for ServiceCard {
                         dvm_kinds.push(kind);
                     }
                 }
+                "price" if values.len() >= 2 => pricing = values[1].parse().ok(),
+                "expiration" if values.len() >= 2 => {
+                    expiration = parse_timestamp("expiration", values[1]).ok();
+                }
+                "hours" if values.len() >= 3 => {
+                    let offset = values.get(3).copied();
+                    if let Ok(window) = AvailabilityWindow::parse(values[1], values[2], offset) {
+                        schedule.push(window);
+                    }
+                }
+                "maintenance" if values.len() >= 3 => {
+                    let start = parse_timestamp("maintenance", values[1]);
+                    let end = parse_timestamp("maintenance", values[2]);
+                    if let (Ok(start), Ok(end)) = (start, end) {
+                        maintenance.push(MaintenanceWindow {
+                            start,
+                            end,
+                            reason: values.get(3).map(|r| r.to_string()),
+                        });
+                    }
+                }
+                "model" if values.len() >= 2 => model = Some(values[1].to_string()),
+                "modality" if values.len() >= 2 => modalities.push(values[1].to_string()),
+                "context" if values.len() >= 2 => context_window = values[1].parse().ok(),
+                "l" if values.len() >= 3 && values[2] == LABEL_LANGUAGE_NAMESPACE => {
+                    languages.push(values[1].to_string());
+                }
+                "tos" if values.len() >= 2 => policy.terms = Some(values[1].to_string()),
+                "privacy" if values.len() >= 2 => policy.privacy = Some(values[1].to_string()),
+                "retention" if values.len() >= 2 => policy.retention = values[1].parse().ok(),
+                "training" if values.len() >= 2 => policy.training = values[1].parse().ok(),
+                "region" if values.len() >= 2 => policy.regions.push(values[1].to_string()),
+                // `sunset` and `successor` imply deprecation even without the marker tag
+                "deprecated" => {
+                    let reason = values.get(1).filter(|r| !r.is_empty());
+                    deprecation.get_or_insert_default().reason = reason.map(|r| r.to_string());
+                }
+                "sunset" if values.len() >= 2 => {
+                    if let Ok(sunset) = parse_timestamp("sunset", values[1]) {
+                        deprecation.get_or_insert_default().sunset = Some(sunset);
+                    }
+                }
+                "a" if values.len() >= 4 && values[3] == SUCCESSOR_MARKER => {
+                    if let Ok(successor) = Deprecation::parse_successor(values[1]) {
+                        deprecation.get_or_insert_default().successor = Some(successor);
+                    }
+                }
+                "overflow" if values.len() >= 2 => overflow = values[1].parse().ok(),
+                "payment" if values.len() >= 2 => {
+                    if let Ok(method) = PaymentMethod::parse(values[1], values.get(2).copied()) {
+                        payment_methods.push(method);
+                    }
+                }
                 _ => {}
             }
         }
@@ -241,6 +1073,19 @@ impl TryFrom<&Event> for ServiceCard {
             about: about.unwrap_or_default(),
             capabilities,
             protocols,
+            content: event.content.clone(),
+            pricing,
+            payment_methods,
+            expiration,
+            schedule,
+            maintenance,
+            model,
+            modalities,
+            context_window,
+            languages,
+            policy,
+            deprecation,
+            overflow,
         })
     }
 }
@@ -252,6 +1097,18 @@ pub struct ServiceCardBuilder {
     about: String,
     capabilities: Vec<Capability>,
     protocols: Vec<Protocol>,
+    content: String,
+    pricing: Option<Pricing>,
+    payment_methods: Vec<PaymentMethod>,
+    expiration: Option<Timestamp>,
+    schedule: Vec<AvailabilityWindow>,
+    maintenance: Vec<MaintenanceWindow>,
+    model: Option<String>,
+    modalities: Vec<String>,
+    context_window: Option<u64>,
+    languages: Vec<String>,
+    policy: Policy,
+    deprecation: Option<Deprecation>,
 }
 
 impl ServiceCardBuilder {
@@ -273,11 +1130,85 @@ impl ServiceCardBuilder {
         self
     }
 
+    /// Add a capability carrying structured metadata.
+    pub fn add_capability(mut self, capability: Capability) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+
     pub fn protocol(mut self, protocol: Protocol) -> Self {
         self.protocols.push(protocol);
         self
     }
 
+    /// Long-form Markdown documentation: usage examples, changelog and so on.
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    /// Default price for capabilities without their own pricing.
+    pub fn pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    pub fn payment_method(mut self, method: PaymentMethod) -> Self {
+        self.payment_methods.push(method);
+        self
+    }
+
+    /// NIP-40 expiration, after which relays may delete the card.
+    pub fn expiration(mut self, expiration: Timestamp) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// Add a weekly window when the agent expects to be up.
+    pub fn availability(mut self, window: AvailabilityWindow) -> Self {
+        self.schedule.push(window);
+        self
+    }
+
+    pub fn maintenance(mut self, window: MaintenanceWindow) -> Self {
+        self.maintenance.push(window);
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn modality(mut self, modality: impl Into<String>) -> Self {
+        self.modalities.push(modality.into());
+        self
+    }
+
+    /// Maximum context size in tokens.
+    pub fn context_window(mut self, tokens: u64) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Add a human language as an ISO 639-1 code, e.g. `de`.
+    pub fn language(mut self, lang: impl Into<String>) -> Self {
+        self.languages.push(lang.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Terms, privacy and data-handling declarations.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Mark the card as retired.
+    pub fn deprecation(mut self, deprecation: Deprecation) -> Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+
     pub fn build(self) -> ServiceCard {
         ServiceCard {
             id: self.id,
@@ -285,6 +1216,180 @@ impl ServiceCardBuilder {
             about: self.about,
             capabilities: self.capabilities,
             protocols: self.protocols,
+            content: self.content,
+            pricing: self.pricing,
+            payment_methods: self.payment_methods,
+            expiration: self.expiration,
+            schedule: self.schedule,
+            maintenance: self.maintenance,
+            model: self.model,
+            modalities: self.modalities,
+            context_window: self.context_window,
+            languages: self.languages,
+            policy: self.policy,
+            deprecation: self.deprecation,
+            overflow: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Retention;
+    use crate::testing;
+
+    /// A minimal card event with extra tags appended.
+    fn card_event(extra: &[&[&str]]) -> Event {
+        let mut tags = ServiceCard::builder("test", "Test Agent").build().to_tags();
+        tags.extend(testing::tags(extra));
+        testing::event(KIND_SERVICE_CARD, tags)
+    }
+
+    #[test]
+    fn malformed_expiration_is_ignored() {
+        let card = ServiceCard::try_from(&card_event(&[&["expiration", "tomorrow"]])).unwrap();
+        assert_eq!(card.expiration, None);
+        assert!(!card.is_expired());
+    }
+
+    #[test]
+    fn malformed_deprecation_values_are_dropped() {
+        let card = ServiceCard::try_from(&card_event(&[
+            &["deprecated", "use v2"],
+            &["sunset", "next week"],
+            &["a", "31990:not-a-pubkey:v2", "", SUCCESSOR_MARKER],
+        ]))
+        .unwrap();
+        assert_eq!(card.deprecation, Some(Deprecation::new().reason("use v2")));
+
+        let card = ServiceCard::try_from(&card_event(&[&["sunset", "soon"]])).unwrap();
+        assert_eq!(card.deprecation, None);
+    }
+
+    #[test]
+    fn malformed_overflow_count_reads_as_unsplit() {
+        let card = ServiceCard::try_from(&card_event(&[&["overflow", "lots"]])).unwrap();
+        assert_eq!(card.overflow, None);
+    }
+
+    #[test]
+    fn mime_wildcards_match_on_both_sides() {
+        assert!(mime_matches("audio/wav", "audio/wav"));
+        assert!(mime_matches("audio/*", "audio/wav"));
+        assert!(mime_matches("audio/wav", "audio/*"));
+        assert!(mime_matches("audio", "audio/wav"));
+        assert!(mime_matches("audio/wav", "audio"));
+        assert!(mime_matches("Audio/WAV", "audio/wav"));
+        assert!(!mime_matches("audio/wav", "audio/mpeg"));
+        assert!(!mime_matches("audio/*", "image/png"));
+        assert!(!mime_matches("image/png", "audio/*"));
+    }
+
+    #[test]
+    fn malformed_max_input_size_is_ignored() {
+        let event = card_event(&[&["c", "audio.transcription", "STT", "max=lots", "in=audio/*"]]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        let cap = &card.capabilities[0];
+        assert_eq!(cap.max_input_bytes, None);
+        assert_eq!(cap.input, ["audio/*"]);
+        assert!(cap.accepts("audio/wav"));
+    }
+
+    #[test]
+    fn capability_versions_round_trip() {
+        let cap = Capability::new("text.translation", "Translate")
+            .version(Version::new(2, 1, 0));
+        let card = ServiceCard::builder("test", "Test Agent").add_capability(cap).build();
+        let parsed = ServiceCard::try_from(&card.to_event(&Keys::generate()).unwrap()).unwrap();
+        assert_eq!(parsed.capabilities[0].version, Some(Version::new(2, 1, 0)));
+
+        let req: CapabilityRequirement = "translation@^2".parse().unwrap();
+        assert!(req.is_satisfied_by(&parsed));
+        let req: CapabilityRequirement = "translation@^3".parse().unwrap();
+        assert!(!req.is_satisfied_by(&parsed));
+    }
+
+    #[test]
+    fn malformed_capability_version_is_ignored() {
+        let event = card_event(&[&["c", "text.translation", "Translate", "v=two"]]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.capabilities[0].id, "text.translation");
+        assert_eq!(card.capabilities[0].version, None);
+    }
+
+    #[test]
+    fn unreadable_windows_are_skipped() {
+        let event = card_event(&[
+            &["hours", "mon-fri", "09:00-17:00", "+01:00"],
+            &["hours", "weekdays", "09:00-17:00"],
+            &["hours", "sat", "9am-5pm"],
+            &["maintenance", "1900000000", "1900003600", "upgrade"],
+            &["maintenance", "tomorrow", "1900003600"],
+        ]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.schedule.len(), 1);
+        assert_eq!(card.schedule[0].utc_offset, 60);
+        assert_eq!(card.maintenance.len(), 1);
+        assert_eq!(card.maintenance[0].reason.as_deref(), Some("upgrade"));
+    }
+
+    #[test]
+    fn malformed_context_size_is_ignored() {
+        let event = card_event(&[&["context", "128k"], &["modality", "image"]]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.context_window, None);
+        assert!(card.supports_modality("image"));
+    }
+
+    #[test]
+    fn languages_match_on_the_primary_subtag() {
+        let card = ServiceCard::builder("test", "Test Agent").language("de").build();
+        let card = ServiceCard::try_from(&card.to_event(&Keys::generate()).unwrap()).unwrap();
+        assert!(card.speaks("de"));
+        assert!(card.speaks("de-CH"));
+        assert!(card.speaks("DE"));
+        assert!(!card.speaks("en"));
+        assert_eq!(primary_language("pt_BR"), "pt");
+    }
+
+    #[test]
+    fn unknown_policy_values_read_as_undeclared() {
+        let event = card_event(&[
+            &["retention", "forever-ish"],
+            &["training", "sometimes"],
+            &["region", "EU"],
+        ]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.policy.retention, None);
+        assert_eq!(card.policy.training, None);
+        assert!(card.policy.is_within_regions(&["EU", "CH"]));
+
+        let event = card_event(&[&["retention", "30d"]]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.policy.retention, Some(Retention::Days { days: 30 }));
+    }
+
+    #[test]
+    fn pricing_round_trips_through_tags() {
+        let card = ServiceCard::builder("test", "Test Agent")
+            .pricing(Pricing::Flat { msats: 21_000 })
+            .add_capability(Capability::new("text.chat", "Chat").pricing(Pricing::Quote))
+            .build();
+        let parsed = ServiceCard::try_from(&card.to_event(&Keys::generate()).unwrap()).unwrap();
+        assert_eq!(parsed.pricing, Some(Pricing::Flat { msats: 21_000 }));
+        assert_eq!(parsed.capabilities[0].pricing, Some(Pricing::Quote));
+    }
+
+    #[test]
+    fn unknown_pricing_is_skipped() {
+        let event = card_event(&[
+            &["price", "auction:5"],
+            &["c", "text.chat", "Chat", "price=per-second:10"],
+        ]);
+        let card = ServiceCard::try_from(&event).unwrap();
+        assert_eq!(card.pricing, None);
+        assert_eq!(card.capabilities[0].id, "text.chat");
+        assert_eq!(card.capabilities[0].pricing, None);
+    }
+}
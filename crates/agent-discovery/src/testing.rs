@@ -0,0 +1,25 @@
+//! Event factories shared by unit tests.
+
+use nostr::{Event, EventBuilder, Keys, Kind, Tag, Timestamp};
+
+/// Parse tags given as string slices, e.g. `&[&["d", "card"], &["s", "available"]]`.
+pub(crate) fn tags(values: &[&[&str]]) -> Vec<Tag> {
+    values
+        .iter()
+        .map(|values| Tag::parse(values.iter().copied()).unwrap())
+        .collect()
+}
+
+/// Sign an event of `kind` with fresh keys.
+pub(crate) fn event(kind: u16, tags: Vec<Tag>) -> Event {
+    event_at(&Keys::generate(), Timestamp::now().as_u64(), kind, tags)
+}
+
+/// Sign an event of `kind` with `keys`, dated `created_at`.
+pub(crate) fn event_at(keys: &Keys, created_at: u64, kind: u16, tags: Vec<Tag>) -> Event {
+    EventBuilder::new(Kind::Custom(kind), "")
+        .tags(tags)
+        .custom_created_at(Timestamp::from(created_at))
+        .sign_with_keys(keys)
+        .unwrap()
+}
@@ -0,0 +1,209 @@
+//! Well-known capability vocabulary
+//!
+//! A curated set of canonical capability ids so that agents offering the same
+//! service are found under the same id. Aliases map common spellings onto the
+//! canonical id.
+
+use crate::service_card::capability_matches;
+
+/// A canonical capability id with its description and accepted aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownCapability {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub aliases: &'static [&'static str],
+    pub category: Option<&'static str>,
+}
+
+/// The built-in registry, ordered by id.
+pub const KNOWN_CAPABILITIES: &[KnownCapability] = &[
+    KnownCapability {
+        id: "audio.transcription",
+        description: "Transcribe speech audio to text",
+        aliases: &["transcription", "speech-to-text", "stt"],
+        category: Some("audio"),
+    },
+    KnownCapability {
+        id: "audio.tts",
+        description: "Synthesize speech audio from text",
+        aliases: &["text-to-speech", "tts", "speech-synthesis"],
+        category: Some("audio"),
+    },
+    KnownCapability {
+        id: "automation",
+        description: "Execute tasks and multi-step workflows",
+        aliases: &["workflow", "task-automation"],
+        category: Some("agents"),
+    },
+    KnownCapability {
+        id: "code.generation",
+        description: "Write code from a specification",
+        aliases: &["coding", "programming", "code-generation"],
+        category: Some("code"),
+    },
+    KnownCapability {
+        id: "code.review",
+        description: "Review code for bugs, style and security",
+        aliases: &["code-review"],
+        category: Some("code"),
+    },
+    KnownCapability {
+        id: "data.extraction",
+        description: "Extract structured data from documents or pages",
+        aliases: &["extraction", "data-extraction", "scraping"],
+        category: Some("data"),
+    },
+    KnownCapability {
+        id: "image.captioning",
+        description: "Describe the contents of an image",
+        aliases: &["captioning", "image-captioning", "image-to-text"],
+        category: Some("image"),
+    },
+    KnownCapability {
+        id: "image.generation",
+        description: "Generate images from a text prompt",
+        aliases: &["image-generation", "text-to-image"],
+        category: Some("image"),
+    },
+    KnownCapability {
+        id: "research",
+        description: "Search and synthesize information",
+        aliases: &["web-search", "search"],
+        category: None,
+    },
+    KnownCapability {
+        id: "text.chat",
+        description: "Natural language conversation",
+        aliases: &["chat", "conversation"],
+        category: Some("text"),
+    },
+    KnownCapability {
+        id: "text.summarization",
+        description: "Summarize documents",
+        aliases: &["summarization", "summarize", "summary"],
+        category: Some("text"),
+    },
+    KnownCapability {
+        id: "text.translation",
+        description: "Translate text between human languages",
+        aliases: &["translation", "translate"],
+        category: Some("text"),
+    },
+];
+
+/// Find a well-known capability by canonical id or alias.
+pub fn lookup(id: &str) -> Option<&'static KnownCapability> {
+    KNOWN_CAPABILITIES
+        .iter()
+        .find(|known| known.id == id || known.aliases.contains(&id))
+}
+
+/// Whether `id` is a well-known id or sits below one (e.g. `code.review.rust`).
+pub fn is_known(id: &str) -> bool {
+    KNOWN_CAPABILITIES
+        .iter()
+        .any(|known| capability_matches(id, known.id))
+}
+
+/// Suggest the closest well-known capability for an unknown id.
+///
+/// Compares against canonical ids and aliases by edit distance and only
+/// suggests reasonably close matches.
+pub fn suggest(id: &str) -> Option<&'static KnownCapability> {
+    let max_distance = (id.len() / 3).max(2);
+
+    KNOWN_CAPABILITIES
+        .iter()
+        .flat_map(|known| {
+            std::iter::once(known.id)
+                .chain(known.aliases.iter().copied())
+                .map(move |candidate| (edit_distance(id, candidate), known))
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// Expand a capability query into itself plus its canonical id and aliases.
+///
+/// Unknown ids expand to just themselves.
+pub fn expand(id: &str) -> Vec<String> {
+    let mut ids = vec![id.to_string()];
+    if let Some(known) = lookup(id) {
+        for candidate in std::iter::once(known.id).chain(known.aliases.iter().copied()) {
+            if candidate != id {
+                ids.push(candidate.to_string());
+            }
+        }
+    }
+    ids
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr.push(substitution.min(prev[j + 1] + 1).min(curr[j] + 1));
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_char_edits() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("translation", "translation"), 0);
+        assert_eq!(edit_distance("summarise", "summarize"), 1);
+    }
+
+    #[test]
+    fn suggest_finds_close_ids_and_aliases() {
+        assert_eq!(suggest("text.sumarization").unwrap().id, "text.summarization");
+        assert_eq!(suggest("translte").unwrap().id, "text.translation");
+        assert_eq!(suggest("summarise").unwrap().id, "text.summarization");
+    }
+
+    #[test]
+    fn suggest_ignores_distant_ids() {
+        assert_eq!(suggest("quantum.annealing"), None);
+        assert_eq!(suggest("xyz"), None);
+    }
+
+    #[test]
+    fn expand_adds_canonical_id_and_aliases() {
+        let ids = expand("summarize");
+        assert_eq!(ids[0], "summarize");
+        assert!(ids.contains(&"text.summarization".to_string()));
+        assert!(ids.contains(&"summary".to_string()));
+        assert_eq!(ids.iter().filter(|id| *id == "summarize").count(), 1);
+
+        let ids = expand("text.translation");
+        assert_eq!(ids, ["text.translation", "translation", "translate"]);
+    }
+
+    #[test]
+    fn expand_keeps_unknown_ids() {
+        assert_eq!(expand("code.review.rust"), ["code.review.rust"]);
+    }
+
+    #[test]
+    fn is_known_covers_descendants() {
+        assert!(is_known("code.review"));
+        assert!(is_known("code.review.rust"));
+        assert!(!is_known("code.reviewer"));
+        assert!(!is_known("coding"));
+    }
+}